@@ -31,7 +31,7 @@ mod tests {
 	#[cfg(feature = "serde")]
 	fn test_fixedlengthstring_ser_de() {
 		let s = ascii::FixedLengthString([ascii::Char(0x41); 1]);
-		assert_tokens(&s, &[Token::BorrowedBytes(&[0x41 as u8])]);
+		assert_tokens(&s, &[Token::BorrowedBytes(&[0x41_u8])]);
 		assert_de_tokens_error::<ascii::FixedLengthString<1>>(
 			&[Token::BorrowedBytes(&[0x41; 2])],
 			"invalid length 2, expected an array of 1 ASCII bytes",
@@ -6,6 +6,8 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Deserializer, Serialize, de::Visitor};
 
+use crate::ascii::ASCIIError;
+
 /// The ascii::Char type represents a single, 8 bit ASCII character.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Char(pub u8);
@@ -68,56 +70,80 @@ impl Char {
 
 	/// Returns true if the ascii::Char is an uppercase letter (A-Z)
 	pub fn is_uppercase(&self) -> bool {
-		match self.0 {
-			0x41..=0x5A => true,
-			_ => false,
-		}
+		matches!(self.0, 0x41..=0x5A)
 	}
 
 	/// Returns true if the ascii::Char is a lowercase letter (a-z)
 	pub fn is_lowercase(&self) -> bool {
-		match self.0 {
-			0x61..=0x7A => true,
-			_ => false,
-		}
+		matches!(self.0, 0x61..=0x7A)
 	}
 
 	/// Returns true if the ascii::Char is a decimal digit (0-9)
 	pub fn is_numeric(&self) -> bool {
-		match self.0 {
-			0x30..=0x39 => true,
-			_ => false,
-		}
+		matches!(self.0, 0x30..=0x39)
 	}
 
 	/// Returns true if the asscii::Char is punctuation
 	pub fn is_punctuation(&self) -> bool {
-		match self.0 {
-			0x21..=0x29 | 0x3A..=0x40 | 0x5B..=0x60 | 0x7B..=0x7E => true,
-			_ => false,
-		}
+		matches!(self.0, 0x21..=0x29 | 0x3A..=0x40 | 0x5B..=0x60 | 0x7B..=0x7E)
 	}
 
 	/// Returns true if the ascii::Char is a control code (0x00-0x1F)
 	pub fn is_control(&self) -> bool {
-		match self.0 {
-			0x00..=0x1F => true,
-			_ => false,
-		}
+		matches!(self.0, 0x00..=0x1F)
 	}
 
 	/// Returns true if the ascii::Char is a whitespace character (e.g. Space, Tab)
 	pub fn is_whitespace(&self) -> bool {
-		match self.0 {
-			0x09 | 0x0A | 0x0C | 0x0D | 0x20 => true,
-			_ => false,
-		}
+		matches!(self.0, 0x09 | 0x0A | 0x0C | 0x0D | 0x20)
 	}
 
 	/// Returns true if the ascii::Char is the NULL character (0x00)
 	pub fn is_null(&self) -> bool {
 		self.0 == 0x00
 	}
+
+	/// Returns true if the ascii::Char is a 7 bit ASCII character (0x00-0x7F)
+	pub fn is_ascii(&self) -> bool {
+		self.0 <= 0x7F
+	}
+
+	/// Returns true if the ascii::Char is defined by ISO 8859-1 (Latin-1), which leaves 0x80-0x9F
+	/// unassigned
+	pub fn is_latin1(&self) -> bool {
+		!matches!(self.0, 0x80..=0x9F)
+	}
+
+	/// Returns the digit for `num` in the given radix, using lowercase letters above 9, or `None`
+	/// if `num` is not less than the radix.
+	///
+	/// Like `char::from_digit`, this panics if the radix is greater than 36.
+	pub fn from_digit(num: u8, radix: u32) -> Option<Char> {
+		assert!(radix <= 36, "from_digit: radix is too high (maximum 36)");
+		if u32::from(num) >= radix {
+			None
+		} else if num < 10 {
+			Some(Char(b'0' + num))
+		} else {
+			Some(Char(b'a' + num - 10))
+		}
+	}
+
+	/// Returns the value of the ascii::Char as a digit in the given radix, accepting letters of
+	/// either case above 9, or `None` if it is not a digit in that radix.
+	///
+	/// Like `char::to_digit`, this panics if the radix is greater than 36.
+	pub fn to_digit(&self, radix: u32) -> Option<u32> {
+		assert!(radix <= 36, "to_digit: radix is too high (maximum 36)");
+		let value = if self.is_numeric() {
+			u32::from(self.0 - b'0')
+		} else if self.is_alphabetic() {
+			u32::from(self.lowercase().0 - b'a') + 10
+		} else {
+			return None;
+		};
+		if value < radix { Some(value) } else { None }
+	}
 }
 
 impl Display for Char {
@@ -130,9 +156,25 @@ impl Display for Char {
 	}
 }
 
+impl TryFrom<char> for Char {
+	type Error = ASCIIError;
+
+	fn try_from(value: char) -> Result<Self, Self::Error> {
+		match u8::try_from(value) {
+			Ok(v) => Ok(Char(v)),
+			Err(_) => Err(ASCIIError {
+				message: format!(
+					"character U+{:04X} is outside the range of an ascii::Char",
+					value as u32
+				),
+			}),
+		}
+	}
+}
+
 impl From<Char> for u8 {
 	fn from(value: Char) -> Self {
-		value.0.clone()
+		value.0
 	}
 }
 
@@ -182,7 +224,7 @@ mod tests {
 	#[test]
 	fn test_utf8() {
 		// Test a specific, known conversion
-		let nbsp = ascii::Char(0xA0 as u8);
+		let nbsp = ascii::Char(0xA0_u8);
 		assert!(nbsp.as_utf8() == [0xC2, 0xA0, 0x00, 0x00]);
 		assert!(nbsp.len_utf8() == 2);
 
@@ -197,6 +239,46 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn test_try_from_char() {
+		assert_eq!(ascii::Char::try_from('A'), Ok(ascii::Char(0x41)));
+		let latin1 = ascii::Char::try_from('é').unwrap();
+		assert_eq!(latin1, ascii::Char(0xE9));
+		assert!(latin1.is_latin1());
+		assert!(!latin1.is_ascii());
+		assert_eq!(latin1.char(), 'é');
+		assert!(ascii::Char::try_from('😶').is_err());
+		assert!(ascii::Char::try_from('\u{100}').is_err());
+		assert!(ascii::Char(0x41).is_ascii());
+		assert!(!ascii::Char(0x85).is_latin1());
+	}
+
+	#[test]
+	fn test_digits() {
+		assert_eq!(ascii::Char(b'9').to_digit(10), Some(9));
+		assert_eq!(ascii::Char(b'A').to_digit(16), Some(10));
+		assert_eq!(ascii::Char(b'f').to_digit(16), Some(15));
+		assert_eq!(ascii::Char(b'z').to_digit(36), Some(35));
+		assert_eq!(ascii::Char(b'A').to_digit(10), None);
+		assert_eq!(ascii::Char(b'8').to_digit(8), None);
+		assert_eq!(ascii::Char(b' ').to_digit(36), None);
+
+		assert_eq!(ascii::Char::from_digit(9, 10), Some(ascii::Char(b'9')));
+		assert_eq!(ascii::Char::from_digit(10, 16), Some(ascii::Char(b'a')));
+		assert_eq!(ascii::Char::from_digit(16, 16), None);
+		// Agrees with char for every digit and radix
+		for radix in 2..=36 {
+			for num in 0..=36 {
+				let c = ascii::Char::from_digit(num, radix);
+				assert_eq!(c.map(|c| c.char()), char::from_digit(u32::from(num), radix));
+				if let Some(c) = c {
+					assert_eq!(c.to_digit(radix), Some(u32::from(num)));
+					assert_eq!(c.uppercase().to_digit(radix), Some(u32::from(num)));
+				}
+			}
+		}
+	}
+
 	#[test]
 	fn test_null() {
 		assert_eq!(ascii::Char::null(), ascii::Char(0x00));
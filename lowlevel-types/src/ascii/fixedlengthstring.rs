@@ -5,7 +5,7 @@ use serde::{Deserialize, Deserializer, Serialize, de::Visitor};
 use crate::ascii::{char::Char, error::ASCIIError};
 
 /// A fixed length ASCII string of length N
-#[derive(Clone, Debug, Hash, PartialOrd)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd)]
 pub struct FixedLengthString<const N: usize>(pub [Char; N]);
 
 impl<const N: usize> FixedLengthString<N> {
@@ -19,9 +19,99 @@ impl<const N: usize> FixedLengthString<N> {
 		self.0.len()
 	}
 
+	/// Returns true if the string has a length of zero
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
 	/// Returns the raw bytes of the string
 	pub fn as_bytes(&self) -> [u8; N] {
-		self.0.map(|c| u8::from(c)).clone()
+		self.0.map(u8::from)
+	}
+
+	/// Consumes the string, returning its raw bytes
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.as_bytes().to_vec()
+	}
+
+	/// Returns the length of the string up to the first `0x00`, leaving out any padding.
+	///
+	/// The padding is kept in the string itself, including when it is serialized and deserialized,
+	/// so that the raw array round trips unchanged.
+	pub fn logical_len(&self) -> usize {
+		self.0.iter().position(|c| *c == Char(0x00)).unwrap_or(N)
+	}
+
+	/// Returns the characters of the string up to the first `0x00`, leaving out any padding
+	pub fn trimmed(&self) -> &[Char] {
+		&self.0[..self.logical_len()]
+	}
+
+	/// Constructs a string from characters, padding it with `0x00` if there are fewer than N.
+	///
+	/// Returns an error if a character is outside the range of an `ascii::Char` or if there are
+	/// more than N characters.
+	pub fn try_from_iter<I>(iter: I) -> Result<Self, ASCIIError>
+	where
+		I: IntoIterator<Item = char>,
+	{
+		let mut s = Self::new();
+		for (i, c) in iter.into_iter().enumerate() {
+			if i >= N {
+				return Err(ASCIIError {
+					message: format!("too many characters for FixedLengthString<{}>", N),
+				});
+			}
+			s.0[i] = Char::try_from(c)?;
+		}
+		Ok(s)
+	}
+
+	/// Returns the index of the first occurrence of `pattern`, or `None` if it does not occur.
+	///
+	/// This is a naive search, taking O(nm) time for a string of length n and a pattern of length m.
+	pub fn find<const M: usize>(&self, pattern: &FixedLengthString<M>) -> Option<usize> {
+		if M == 0 {
+			return Some(0);
+		}
+		self.0.windows(M).position(|w| w == pattern.0)
+	}
+
+	/// Returns true if `pattern` occurs in the string
+	pub fn contains<const M: usize>(&self, pattern: &FixedLengthString<M>) -> bool {
+		self.find(pattern).is_some()
+	}
+
+	/// Returns a copy of the string with each non-overlapping occurrence of `from` replaced by `to`,
+	/// scanning from the start.
+	///
+	/// The replacement must be the same length as the pattern, so that the string keeps its
+	/// length. Like `find`, this takes O(nm) time.
+	pub fn replace<const M: usize>(
+		&self,
+		from: &FixedLengthString<M>,
+		to: &FixedLengthString<M>,
+	) -> Self {
+		let mut result = self.clone();
+		if M == 0 {
+			return result;
+		}
+		let mut i = 0;
+		while i + M <= N {
+			if self.0[i..i + M] == from.0 {
+				result.0[i..i + M].copy_from_slice(&to.0);
+				i += M;
+			} else {
+				i += 1;
+			}
+		}
+		result
+	}
+}
+
+impl<const N: usize> Default for FixedLengthString<N> {
+	fn default() -> Self {
+		Self::new()
 	}
 }
 
@@ -34,12 +124,6 @@ impl<const N: usize> Display for FixedLengthString<N> {
 	}
 }
 
-impl<const N: usize> PartialEq<FixedLengthString<N>> for FixedLengthString<N> {
-	fn eq(&self, other: &FixedLengthString<N>) -> bool {
-		self.0 == other.0
-	}
-}
-
 impl<const N: usize> PartialEq<&str> for FixedLengthString<N> {
 	fn eq(&self, other: &&str) -> bool {
 		let s = String::from(self.clone());
@@ -68,16 +152,31 @@ impl<const N: usize> TryFrom<&[u8]> for FixedLengthString<N> {
 		} else {
 			let v: &mut [Char; N] = &mut [Char(0x00); N];
 			for i in 0..value.len() {
-				v[i] = Char(value[i].clone());
+				v[i] = Char(value[i]);
 			}
-			Ok(FixedLengthString(v.clone()))
+			Ok(FixedLengthString(*v))
+		}
+	}
+}
+
+/// Collects characters into a string, padding it with `0x00` if there are fewer than N.
+///
+/// Panics if there are more than N characters, like `From<Vec<u8>>`; use `try_from_iter` to
+/// handle that case.
+impl<const N: usize> FromIterator<Char> for FixedLengthString<N> {
+	fn from_iter<I: IntoIterator<Item = Char>>(iter: I) -> Self {
+		let mut s = Self::new();
+		for (i, c) in iter.into_iter().enumerate() {
+			assert!(i < N, "too many characters for FixedLengthString<{}>", N);
+			s.0[i] = c;
 		}
+		s
 	}
 }
 
 impl<const N: usize> From<[u8; N]> for FixedLengthString<N> {
 	fn from(value: [u8; N]) -> Self {
-		FixedLengthString(value.map(|c| Char(c)))
+		FixedLengthString(value.map(Char))
 	}
 }
 
@@ -95,14 +194,10 @@ impl<const N: usize> TryFrom<&str> for FixedLengthString<N> {
 	fn try_from(value: &str) -> Result<Self, Self::Error> {
 		if !value.is_ascii() {
 			Err(ASCIIError {
-				message: format!("attempt to convert an Unicode string to an AString"),
+				message: String::from("attempt to convert an Unicode string to an AString"),
 			})
 		} else {
-			let v: &mut Vec<u8> = &mut Vec::new();
-			for c in value.chars() {
-				v.push(c as u8);
-			}
-			Ok(FixedLengthString::from(v.clone()))
+			FixedLengthString::<N>::try_from(value.as_bytes())
 		}
 	}
 }
@@ -117,6 +212,12 @@ impl<const N: usize> From<FixedLengthString<N>> for String {
 	}
 }
 
+impl<const N: usize> From<FixedLengthString<N>> for Vec<u8> {
+	fn from(value: FixedLengthString<N>) -> Self {
+		value.into_bytes()
+	}
+}
+
 #[cfg(feature = "serde")]
 impl<const N: usize> Serialize for FixedLengthString<N> {
 	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -163,6 +264,8 @@ impl<'de, const N: usize> Visitor<'de> for AStringVisitor<N> {
 
 #[cfg(test)]
 mod tests {
+	use std::collections::HashSet;
+
 	use crate::ascii::{self, char::Char, fixedlengthstring::FixedLengthString};
 
 	#[test]
@@ -172,22 +275,30 @@ mod tests {
 		assert_eq!(s1, "\0");
 		let s: FixedLengthString<10> = FixedLengthString::new();
 		assert_eq!(s.len(), 10);
+
+		let s0: FixedLengthString<0> = FixedLengthString::new();
+		assert!(s0.is_empty());
+		assert_eq!(s0, "");
+		assert_eq!(FixedLengthString::<0>::try_from("").unwrap(), s0);
+		assert_eq!(FixedLengthString::<0>::from(Vec::new()), s0);
+		assert!(FixedLengthString::<0>::try_from("A").is_err());
+		assert_eq!(String::from(s0), "");
 	}
 
 	#[test]
 	fn test_from_vec_of_u8() {
-		let v: Vec<u8> = vec![0x41 as u8];
+		let v: Vec<u8> = vec![0x41_u8];
 		let s1: FixedLengthString<1> = FixedLengthString::from(v);
 		assert_eq!(s1, "A");
 	}
 
 	#[test]
 	fn test_from_array_of_u8() {
-		let s1: FixedLengthString<1> = FixedLengthString::try_from(&[0x41 as u8] as &[u8]).unwrap();
+		let s1: FixedLengthString<1> = FixedLengthString::try_from(&[0x41_u8] as &[u8]).unwrap();
 		assert_eq!(s1, "A");
-		let s2: FixedLengthString<1> = FixedLengthString::from([0x41 as u8; 1]);
+		let s2: FixedLengthString<1> = FixedLengthString::from([0x41_u8; 1]);
 		assert_eq!(s2, "A");
-		assert!(FixedLengthString::<1>::try_from(&[0x41 as u8; 2] as &[u8]).is_err());
+		assert!(FixedLengthString::<1>::try_from(&[0x41_u8; 2] as &[u8]).is_err());
 	}
 
 	#[test]
@@ -203,6 +314,102 @@ mod tests {
 		assert_eq!(s4, "A");
 	}
 
+	#[test]
+	fn test_into_bytes() {
+		let s = FixedLengthString::<3>::try_from("ABC").unwrap();
+		assert_eq!(s.as_bytes(), [0x41, 0x42, 0x43]);
+		assert_eq!(s.clone().into_bytes(), vec![0x41, 0x42, 0x43]);
+		assert_eq!(Vec::<u8>::from(s), vec![0x41, 0x42, 0x43]);
+		assert!(FixedLengthString::<0>::new().into_bytes().is_empty());
+	}
+
+	#[test]
+	fn test_logical_len() {
+		let s = FixedLengthString::<4>::try_from("AB\0\0").unwrap();
+		assert_eq!(s.len(), 4);
+		assert_eq!(s.logical_len(), 2);
+		assert_eq!(s.trimmed(), [Char(0x41), Char(0x42)]);
+		// Only the padding after the first `0x00` is left out
+		let s = FixedLengthString::<4>::try_from("A\0B\0").unwrap();
+		assert_eq!(s.logical_len(), 1);
+		let s = FixedLengthString::<2>::try_from("AB").unwrap();
+		assert_eq!(s.logical_len(), 2);
+		assert_eq!(FixedLengthString::<3>::new().logical_len(), 0);
+		assert!(FixedLengthString::<0>::new().trimmed().is_empty());
+	}
+
+	#[test]
+	fn test_find() {
+		let s = FixedLengthString::<5>::try_from("aXbXc").unwrap();
+		let x = FixedLengthString::<1>::try_from("X").unwrap();
+		assert_eq!(s.find(&x), Some(1));
+		assert_eq!(
+			s.find(&FixedLengthString::<2>::try_from("bX").unwrap()),
+			Some(2)
+		);
+		assert_eq!(
+			s.find(&FixedLengthString::<2>::try_from("ab").unwrap()),
+			None
+		);
+		assert_eq!(
+			s.find(&FixedLengthString::<6>::try_from("aXbXcX").unwrap()),
+			None
+		);
+		assert_eq!(s.find(&FixedLengthString::<0>::new()), Some(0));
+		assert!(s.contains(&x));
+		assert!(s.contains(&s));
+		assert!(!s.contains(&FixedLengthString::<1>::try_from("-").unwrap()));
+	}
+
+	#[test]
+	fn test_replace() {
+		let s = FixedLengthString::<5>::try_from("aXbXc").unwrap();
+		let x = FixedLengthString::<1>::try_from("X").unwrap();
+		let dash = FixedLengthString::<1>::try_from("-").unwrap();
+		assert_eq!(s.replace(&x, &dash), "a-b-c");
+		assert_eq!(s.replace(&dash, &x), "aXbXc");
+
+		// Occurrences are replaced from the start without overlapping
+		let s = FixedLengthString::<5>::try_from("aaaaa").unwrap();
+		let aa = FixedLengthString::<2>::try_from("aa").unwrap();
+		let bb = FixedLengthString::<2>::try_from("bb").unwrap();
+		assert_eq!(s.replace(&aa, &bb), "bbbba");
+	}
+
+	#[test]
+	fn test_from_iter() {
+		let s: FixedLengthString<1> = vec![Char(0x41)].into_iter().collect();
+		assert_eq!(s, "A");
+		let s: FixedLengthString<3> = "AB".bytes().map(Char).collect();
+		assert_eq!(s, "AB\0");
+
+		let s = FixedLengthString::<3>::try_from_iter("ABC".chars()).unwrap();
+		assert_eq!(s, "ABC");
+		let s = FixedLengthString::<2>::try_from_iter("\u{E9}".chars()).unwrap();
+		assert_eq!(s.as_bytes(), [0xE9, 0x00]);
+		assert!(FixedLengthString::<3>::try_from_iter("ABCD".chars()).is_err());
+		assert!(FixedLengthString::<3>::try_from_iter("A\u{1F636}".chars()).is_err());
+	}
+
+	#[test]
+	#[should_panic]
+	fn test_from_iter_too_long() {
+		let _: FixedLengthString<1> = vec![Char(0x41), Char(0x42)].into_iter().collect();
+	}
+
+	#[test]
+	fn test_hash() {
+		let mut set: HashSet<FixedLengthString<4>> = HashSet::new();
+		// Strings built in different ways with the same characters, padding included, are equal
+		assert!(set.insert(FixedLengthString::try_from("AB").unwrap()));
+		assert!(!set.insert(FixedLengthString::from([0x41, 0x42, 0x00, 0x00])));
+		assert!(!set.insert("AB".bytes().map(Char).collect()));
+		assert_eq!(set.len(), 1);
+		// The padding is part of the string
+		assert!(set.insert(FixedLengthString::try_from("AB\0C").unwrap()));
+		assert_eq!(set.len(), 2);
+	}
+
 	#[test]
 	fn test_display() {
 		let s = FixedLengthString([ascii::Char(0x41); 1]);
@@ -7,15 +7,41 @@
 //!
 //! - Serialization and deserialization of Rust data structures to and from binary format
 //! - Full support for ASCII (through `lowlevel-types`) and UTF-8 characters and strings
+//! - Fixed length ASCII strings in the same format as `String` through the `astring_as_string` module
 //! - Support for Big Endian and Little Endian (default) encoding
 //! - Comprehensive error reporting
 //! - Compression of `usize` markers for sequences and structures
 //! - Support for `u128` and `i128` types
 //! - Enums and variants stored as `u32`
+//! - Delta encoding of sorted integer sequences through the `delta` module
+//! - Run-length encoding of byte buffers, such as masks, through the `byte_rle` module
+//! - Signed integers of small magnitude in fewer bytes through the `zigzag` module
+//! - Signed integers in sign-magnitude form, for legacy formats, through the `sign_magnitude` module
+//! - Single byte C-style enums through the `c_enum` module
+//! - Lists of structs written column by column, for analytical data, through the `columnar` module
+//! - Enums written as their explicit discriminant values through the `discriminant` module
+//! - Options of non-zero numbers without a flag byte through the `compact_option` module
+//! - Fixed point numbers through the `fixed_point` module
+//! - Strings of a fixed number of bytes, for fixed layout records, through the `fixed_str` module
+//! - Zero byte fields for collections that are always empty through the `omit_empty` module
+//! - Optional presence bitmaps for structs with many `Option` fields, enabled through `Config`
+//! - Size limited stream output through `stream::LimitedWriter`
+//! - Append-only sequences written and read one element at a time through `stream::SeqWriter` and `stream::SeqReader`
+//! - Optional schema fingerprints that reject data written for a different struct, enabled through `Config`
+//! - Paths and OS strings that are not UTF-8 through the `os_str` module
+//! - An optional self-describing mode, enabled through `Config`, that tags every value with its type and writes structs keyed by field name
+//! - A canonical preset, `Config::canonical()`, that writes the same bytes for equal values, for content addressed storage
+//! - Optional string interning, enabled through `Config`, that writes repeated strings as a reference to the first occurrence
+//! - Records of mixed types, written with a `u16` type id and read back through a `TypeRegistry`
+//! - Checks of self-describing data against a `Schema` of field types, naming the field that differs
+//! - Incremental decoding of bytes pushed a piece at a time through `IncrementalDeserializer`
 //!
 //! ## Limitations
 //!
-//! - No support foe serializing or deserializing sequences or maps of unknown length
+//! - No support for serializing sequences or maps of unknown length with the streaming Serializer
+//! - Types that need `deserialize_any`, such as internally tagged and untagged enums, can only be read in self-describing mode. Adjacently tagged enums also require it.
+//! - `Weak` pointers are not supported: serde only implements them with its `rc` feature, and a deserialized `Weak` never points to a value. `Cell` and `RefCell` are written as their contents.
+//! - Fields skipped with `#[serde(skip_serializing_if)]` are only supported in self-describing mode, where they are left out and filled with their defaults when read. Other modes write fields by position, so the fields after a skipped one would be misread.
 //!
 //! ## Installation
 //!
@@ -37,9 +63,9 @@
 //!
 //! ```rust
 //! use serde::{Serialize, Deserialize};
-//! use serde_binary_adv::{Serializer, Deserializer, BinaryError, Result}
+//! use serde_binary_adv::{Deserializer, Serializer};
 //!
-//! # [derive(Serialize, Deserialize)]
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
 //! struct Point {
 //!     x: f64,
 //!     y: f64,
@@ -50,7 +76,7 @@
 //!
 //!     let serialized = Serializer::to_bytes(&point, false).unwrap();
 //!     let deserialized: Point = Deserializer::from_bytes(&serialized, false).unwrap();
-//! 	assert_eq!(value, deserialized,);
+//!     assert_eq!(point, deserialized);
 //! }
 //! ```
 //!
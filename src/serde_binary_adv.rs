@@ -1,27 +1,76 @@
+pub mod astring_as_string;
 mod binaryerror;
+pub mod byte_rle;
+pub mod c_enum;
+pub mod columnar;
 mod common;
+pub mod compact_option;
+mod config;
 mod de;
+pub mod delta;
+pub mod discriminant;
+pub mod fixed_point;
+pub mod fixed_str;
+mod incremental;
+pub mod omit_empty;
+pub mod os_str;
+mod registry;
+mod schema;
 mod ser;
+pub mod sign_magnitude;
 
 #[cfg(feature = "streaming")]
 pub mod stream;
+pub mod zigzag;
 
 pub use binaryerror::BinaryError;
 pub use common::{ByteFormat, Result};
+pub use config::{Config, StringType};
 pub use de::Deserializer;
+pub use incremental::IncrementalDeserializer;
+pub use registry::TypeRegistry;
+pub use schema::{Schema, ValueType};
 pub use ser::Serializer;
 
 #[cfg(test)]
 mod tests {
-	use std::collections::HashMap;
+	use std::{
+		borrow::Cow,
+		cell::{Cell, RefCell},
+		collections::{BTreeMap, HashMap},
+		marker::PhantomData,
+		path::PathBuf,
+		thread,
+	};
 
+	use assert_no_alloc::{AllocDisabler, assert_no_alloc, reset_violation_count, violation_count};
+	use indexmap::IndexMap;
+	use lowlevel_types::ascii::FixedLengthString;
+	use ordered_float::OrderedFloat;
 	use serde::{Deserialize, Serialize};
+	use serde_big_array::BigArray;
+	use serde_bytes::{ByteBuf, Bytes};
+	use serde_with::{DisplayFromStr, serde_as};
+	use static_assertions::assert_impl_all;
+	use uuid::Uuid;
 
-	use crate::{Deserializer, Serializer};
+	use crate::serde_binary_adv::common::compress_usize;
+	use crate::{BinaryError, ByteFormat, Config, Deserializer, Serializer, StringType};
+
+	assert_impl_all!(Serializer: Send);
+	assert_impl_all!(Deserializer<'static>: Send);
 
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
 	struct Unit;
 
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Empty {}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Single {
+		pub byte: u8,
+	}
+
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
 	struct NewType(u8);
 
@@ -35,6 +84,25 @@ mod tests {
 	}
 
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Phantom {
+		pub byte: u8,
+		pub marker: PhantomData<String>,
+		pub string: String,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	struct Arrays {
+		#[serde(with = "BigArray")]
+		pub bytes: [u8; 256],
+		#[serde(with = "BigArray")]
+		pub words: [u32; 64],
+		pub floats: [f64; 16],
+		#[serde(with = "BigArray")]
+		pub longs: [u64; 256],
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	#[allow(clippy::enum_variant_names)]
 	enum TestEnum {
 		NewTypeVariant(u8),
 		StructVariant { a: u8, b: u8 },
@@ -57,17 +125,29 @@ mod tests {
 	impl_test_x!(test_bool_true, true);
 	impl_test_x!(test_bool_false, false);
 
-	impl_test_x!(test_u8, 0x41 as u8);
-	impl_test_x!(test_u16, 0x41 as u16);
-	impl_test_x!(test_u32, 0x41 as u32);
-	impl_test_x!(test_u64, 0x41 as u64);
-	impl_test_x!(test_u128, 0x41 as u128);
+	impl_test_x!(test_u8, 0x41_u8);
+	impl_test_x!(test_u16, 0x41_u16);
+	impl_test_x!(test_u32, 0x41_u32);
+	impl_test_x!(test_u64, 0x41_u64);
+	impl_test_x!(test_u128, 0x41_u128);
 
-	impl_test_x!(test_i8, 0x41 as i8);
-	impl_test_x!(test_i16, 0x41 as i16);
-	impl_test_x!(test_i32, 0x41 as i32);
-	impl_test_x!(test_i64, 0x41 as i64);
-	impl_test_x!(test_i128, 0x41 as i128);
+	impl_test_x!(test_i8, 0x41_i8);
+	impl_test_x!(test_i16, 0x41_i16);
+	impl_test_x!(test_i32, 0x41_i32);
+	impl_test_x!(test_i64, 0x41_i64);
+	impl_test_x!(test_i128, 0x41_i128);
+	impl_test_x!(test_u128_max, u128::MAX);
+	impl_test_x!(test_i128_min, i128::MIN);
+
+	#[test]
+	fn test_u128_byte_order() {
+		let value = 0x0102_0304_0506_0708_090A_0B0C_0D0E_0F10_u128;
+		let serialized = Serializer::to_bytes(&value, true).unwrap();
+		assert_eq!(serialized, value.to_be_bytes().to_vec());
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, value.to_le_bytes().to_vec());
+		assert!(Deserializer::from_bytes::<u128>(&serialized[..15], false).is_err());
+	}
 
 	impl_test_x!(test_f32, 0x41 as f32);
 	impl_test_x!(test_f64, 0x41 as f64);
@@ -79,17 +159,613 @@ mod tests {
 
 	#[test]
 	fn test_bad_char() {
-		assert!(Deserializer::from_bytes::<char>(&vec![0x80 as u8], false).is_err());
-		assert!(Deserializer::from_bytes::<char>(&vec![0xC0 as u8, 0x00 as u8], false).is_err());
-		assert!(Deserializer::from_bytes::<char>(&vec![], false).is_err());
+		assert!(Deserializer::from_bytes::<char>(&[0x80_u8], false).is_err());
+		assert!(Deserializer::from_bytes::<char>(&[0xC0_u8, 0x00_u8], false).is_err());
+		assert!(Deserializer::from_bytes::<char>(&[], false).is_err());
 	}
 
 	// Test Serde String
 	impl_test_x!(test_string, String::from("test"));
 
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Borrowing<'a> {
+		#[serde(borrow)]
+		pub text: Cow<'a, str>,
+		#[serde(borrow)]
+		pub bytes: Cow<'a, [u8]>,
+	}
+
+	#[test]
+	fn test_borrowed_cow() {
+		let value = Borrowing {
+			text: Cow::Owned(String::from("test")),
+			bytes: Cow::Owned(vec![0x41, 0x42, 0x43]),
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let deserialized: Borrowing = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
+		assert!(matches!(deserialized.text, Cow::Borrowed("test")));
+		assert!(matches!(
+			deserialized.bytes,
+			Cow::Borrowed(&[0x41, 0x42, 0x43])
+		));
+	}
+
+	#[test]
+	fn test_borrow_from_file() {
+		// Data read or mapped from a file is deserialized in place, so large fields point into it
+		// rather than being copied
+		let value = Borrowing {
+			text: Cow::Owned("test".repeat(0x10000)),
+			bytes: Cow::Owned(vec![0xA5; 0x100000]),
+		};
+		let name = format!("serde_binary_adv_{}.bin", std::process::id());
+		let path = std::env::temp_dir().join(name);
+		std::fs::write(&path, Serializer::to_bytes(&value, false).unwrap()).unwrap();
+		let data = std::fs::read(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		let deserialized: Borrowing = Deserializer::from_bytes(&data, false).unwrap();
+		assert_eq!(value, deserialized);
+		let range = data.as_ptr_range();
+		for field in [deserialized.text.as_bytes(), &deserialized.bytes] {
+			assert!(range.contains(&field.as_ptr()));
+		}
+		assert!(matches!(deserialized.text, Cow::Borrowed(_)));
+		assert!(matches!(deserialized.bytes, Cow::Borrowed(_)));
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct ByteFields<'a> {
+		#[serde(borrow)]
+		pub borrowed: &'a Bytes,
+		pub owned: ByteBuf,
+	}
+
+	#[test]
+	fn test_serde_bytes() {
+		let value = ByteFields {
+			borrowed: Bytes::new(&[0x41, 0x42, 0x43]),
+			owned: ByteBuf::from(vec![0x44; 0x80]),
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Struct length, then each field as its compressed length and the raw bytes
+		let mut expected = vec![0x02, 0x03, 0x41, 0x42, 0x43, 0x80, 0x01];
+		expected.extend(vec![0x44; 0x80]);
+		assert_eq!(serialized, expected);
+		let deserialized: ByteFields = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
+		// The borrowed field points into the input rather than a copy
+		assert!(std::ptr::eq(deserialized.borrowed.as_ptr(), &serialized[2]));
+
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: ByteFields =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	// Reports which visit method the Deserializer called for a byte array
+	struct BytesVisitor;
+
+	impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+		type Value = (&'static str, Vec<u8>);
+
+		fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+			formatter.write_str("a byte array")
+		}
+
+		fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+			Ok(("visit_borrowed_bytes", v.to_vec()))
+		}
+
+		fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+			Ok(("visit_bytes", v.to_vec()))
+		}
+
+		fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+			Ok(("visit_byte_buf", v))
+		}
+	}
+
+	#[test]
+	fn test_byte_buf_visitor() {
+		use serde::Deserializer as _;
+
+		let serialized = Serializer::to_bytes(&Bytes::new(&[0x41, 0x42]), false).unwrap();
+		let mut deserializer = Deserializer::new(&serialized, false);
+		let visited = deserializer.deserialize_byte_buf(BytesVisitor).unwrap();
+		assert_eq!(visited, ("visit_byte_buf", vec![0x41, 0x42]));
+		let mut deserializer = Deserializer::new(&serialized, false);
+		let visited = deserializer.deserialize_bytes(BytesVisitor).unwrap();
+		assert_eq!(visited, ("visit_borrowed_bytes", vec![0x41, 0x42]));
+
+		let deserialized: ByteBuf = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, ByteBuf::from(vec![0x41, 0x42]));
+		let deserialized: &Bytes = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, Bytes::new(&[0x41, 0x42]));
+	}
+
+	// Records the size hint given for a sequence, then reads it as normal
+	struct SeqHint<T>(Option<usize>, Vec<T>);
+
+	impl<'de, T: Deserialize<'de>> Deserialize<'de> for SeqHint<T> {
+		fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+		where
+			D: serde::Deserializer<'de>,
+		{
+			struct SeqHintVisitor<T>(PhantomData<T>);
+
+			impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for SeqHintVisitor<T> {
+				type Value = SeqHint<T>;
+
+				fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+					formatter.write_str("a sequence")
+				}
+
+				fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+				where
+					A: serde::de::SeqAccess<'de>,
+				{
+					let hint = seq.size_hint();
+					let mut values = Vec::new();
+					while let Some(value) = seq.next_element()? {
+						values.push(value);
+					}
+					Ok(SeqHint(hint, values))
+				}
+			}
+
+			deserializer.deserialize_seq(SeqHintVisitor(PhantomData))
+		}
+	}
+
+	#[test]
+	fn test_seq_size_hint() {
+		let value: Vec<u64> = (0..10_000).collect();
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let deserialized: SeqHint<u64> = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized.0, Some(10_000));
+		assert_eq!(deserialized.1, value);
+		// The Vec is allocated once with the exact size, rather than grown as it is read
+		let deserialized: Vec<u64> = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, value);
+		assert_eq!(deserialized.capacity(), 10_000);
+
+		// The hint is never more than the input left, so a hostile length cannot force a huge
+		// allocation. Units take no space, so they are the only values the hint undercounts.
+		let serialized = Serializer::to_bytes(&vec![(); 1000], false).unwrap();
+		let deserialized: SeqHint<()> = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized.0, Some(0));
+		assert_eq!(deserialized.1.len(), 1000);
+	}
+
+	// Records the size hint given for a map, then reads it as normal
+	struct MapHint(Option<usize>, HashMap<u32, u32>);
+
+	impl<'de> Deserialize<'de> for MapHint {
+		fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+		where
+			D: serde::Deserializer<'de>,
+		{
+			struct MapHintVisitor;
+
+			impl<'de> serde::de::Visitor<'de> for MapHintVisitor {
+				type Value = MapHint;
+
+				fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+					formatter.write_str("a map")
+				}
+
+				fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+				where
+					A: serde::de::MapAccess<'de>,
+				{
+					let hint = map.size_hint();
+					let mut values = HashMap::new();
+					while let Some((key, value)) = map.next_entry()? {
+						values.insert(key, value);
+					}
+					Ok(MapHint(hint, values))
+				}
+			}
+
+			deserializer.deserialize_map(MapHintVisitor)
+		}
+	}
+
+	#[test]
+	fn test_map_size_hint() {
+		let value: HashMap<u32, u32> = (0..10_000).map(|v| (v, v * 2)).collect();
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let deserialized: MapHint = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized.0, Some(10_000));
+		assert_eq!(deserialized.1, value);
+		let deserialized: HashMap<u32, u32> = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, value);
+		assert!(deserialized.capacity() >= 10_000);
+	}
+
+	// Writes the even numbers below the limit, without an exact size hint
+	struct Evens(u32);
+
+	impl Serialize for Evens {
+		fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+		where
+			S: serde::Serializer,
+		{
+			serializer.collect_seq((0..).filter(|v| v % 2 == 0).take_while(|v| *v < self.0))
+		}
+	}
+
+	// Maps each of the even numbers below the limit to half its value
+	struct Halves(u32);
+
+	impl Serialize for Halves {
+		fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+		where
+			S: serde::Serializer,
+		{
+			serializer.collect_map((0..self.0).filter(|v| v % 2 == 0).map(|v| (v, v / 2)))
+		}
+	}
+
+	#[test]
+	fn test_unknown_length() {
+		for limit in [0, 6, 200, 400] {
+			let evens: Vec<u32> = (0..limit).filter(|v| v % 2 == 0).collect();
+			let halves: BTreeMap<u32, u32> = evens.iter().map(|v| (*v, v / 2)).collect();
+			for config in [
+				Config::new(false),
+				Config::new(true),
+				Config {
+					varint_endianness: ByteFormat::BigEndian,
+					..Config::default()
+				},
+				Config {
+					self_describing: true,
+					..Config::default()
+				},
+			] {
+				let serialized = Serializer::to_bytes_with_config(&Evens(limit), &config).unwrap();
+				let expected = Serializer::to_bytes_with_config(&evens, &config).unwrap();
+				assert_eq!(serialized, expected);
+
+				let serialized = Serializer::to_bytes_with_config(&Halves(limit), &config).unwrap();
+				let expected = Serializer::to_bytes_with_config(&halves, &config).unwrap();
+				assert_eq!(serialized, expected);
+			}
+		}
+
+		// Collections of unknown length nested in each other and in known ones
+		let value = (Evens(300), vec![Evens(4), Evens(0)], Halves(10));
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let deserialized: (Vec<u32>, Vec<Vec<u32>>, BTreeMap<u32, u32>) =
+			Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized.0.len(), 150);
+		assert_eq!(deserialized.1, vec![vec![0, 2], vec![]]);
+		assert_eq!(deserialized.2.len(), 5);
+	}
+
+	#[derive(Serialize, Deserialize, Debug)]
+	struct Record {
+		pub counts: HashMap<String, u32>,
+		pub ratio: f64,
+		pub values: Vec<u8>,
+	}
+
+	#[test]
+	fn test_canonical() {
+		let record = |keys: &[&str], ratio: f64| Record {
+			counts: keys
+				.iter()
+				.map(|k| (String::from(*k), k.len() as u32))
+				.collect(),
+			ratio,
+			values: vec![0x41, 0x42],
+		};
+		let keys = [
+			"a", "bb", "ccc", "dddd", "eeeee", "ffffff", "ggggggg", "hhhhhhhh",
+		];
+		let mut reversed = keys;
+		reversed.reverse();
+		let config = Config::canonical();
+		let first = Serializer::to_bytes_with_config(&record(&keys, f64::NAN), &config).unwrap();
+		// A negative NaN with a different payload, and a map built in a different order
+		let second = Serializer::to_bytes_with_config(
+			&record(&reversed, -f64::from_bits(0x7FF0_0000_0000_0001)),
+			&config,
+		)
+		.unwrap();
+		assert_eq!(first, second);
+		assert_eq!(
+			Serializer::to_bytes_with_config(&record(&keys, f64::NAN), &config).unwrap(),
+			first
+		);
+		// The entries are sorted, so "a" comes first, and the NaN is the standard quiet NaN
+		assert_eq!(first[0..6], [0x03, 0x08, 0x01, b'a', 0x01, 0x00]);
+		let nan = first.len() - 11;
+		assert_eq!(first[nan..nan + 8], f64::NAN.to_le_bytes());
+
+		// The output can be read with the default settings
+		let deserialized: Record = Deserializer::from_bytes(&first, false).unwrap();
+		assert_eq!(deserialized.counts, record(&keys, 0.0).counts);
+		assert!(deserialized.ratio.is_nan());
+		assert_eq!(deserialized.values, vec![0x41, 0x42]);
+
+		// Maps nested in maps are sorted too
+		let mut nested: HashMap<u8, HashMap<u8, u8>> = HashMap::new();
+		for i in 0..16 {
+			nested.insert(i, (0..i).map(|j| (j, i)).collect());
+		}
+		let sorted: BTreeMap<u8, BTreeMap<u8, u8>> = nested
+			.iter()
+			.map(|(k, v)| (*k, v.iter().map(|(a, b)| (*a, *b)).collect()))
+			.collect();
+		assert_eq!(
+			Serializer::to_bytes_with_config(&nested, &config).unwrap(),
+			Serializer::to_bytes(&sorted, false).unwrap()
+		);
+	}
+
+	#[test]
+	fn test_fixed_length_string() {
+		let value = FixedLengthString::<0>::new();
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, vec![0x00]);
+		test(value.clone());
+		test_sd(value);
+
+		let value = FixedLengthString::<3>::try_from("ABC").unwrap();
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, vec![0x03, 0x41, 0x42, 0x43]);
+		test(value.clone());
+		test_sd(value);
+		assert!(Deserializer::from_bytes::<FixedLengthString<0>>(&serialized, false).is_err());
+
+		// Padding is kept when reading, and can be trimmed afterwards
+		let value = FixedLengthString::<4>::try_from("AB").unwrap();
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, vec![0x04, 0x41, 0x42, 0x00, 0x00]);
+		let deserialized: FixedLengthString<4> =
+			Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, value);
+		assert_eq!(deserialized.logical_len(), 2);
+	}
+
+	#[derive(Deserialize, Debug)]
+	enum Never {}
+
+	#[test]
+	fn test_empty_enum() {
+		for serialized in [vec![], vec![0xFE, 0x00, 0x00, 0x00, 0x00]] {
+			match Deserializer::from_bytes::<Never>(&serialized, false) {
+				Err(BinaryError::Message { message }) => assert!(message.contains("no variants")),
+				other => panic!("unexpected result {:?}", other),
+			}
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Leaf<'a> {
+		pub name: &'a str,
+		#[serde(with = "serde_bytes")]
+		pub bytes: &'a [u8],
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Branch<'a> {
+		#[serde(borrow)]
+		pub leaf: Leaf<'a>,
+		#[serde(borrow)]
+		pub leaves: Vec<Leaf<'a>>,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Tree<'a> {
+		#[serde(borrow)]
+		Branch(Branch<'a>),
+		Leaf {
+			#[serde(borrow)]
+			leaf: Option<Leaf<'a>>,
+		},
+	}
+
+	#[test]
+	fn test_nested_borrow() {
+		let leaf = |name| Leaf {
+			name,
+			bytes: &[0x41, 0x42],
+		};
+		let value = vec![
+			Tree::Branch(Branch {
+				leaf: leaf("first"),
+				leaves: vec![leaf("second"), leaf("third")],
+			}),
+			Tree::Leaf {
+				leaf: Some(leaf("fourth")),
+			},
+		];
+		for config in [
+			Config::default(),
+			Config {
+				bitmap_options: true,
+				..Config::new(true)
+			},
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+		] {
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let deserialized: Vec<Tree> =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+			// Every string and byte array points into the serialized data
+			let range = serialized.as_ptr_range();
+			let Tree::Branch(branch) = &deserialized[0] else {
+				panic!("unexpected variant");
+			};
+			for leaf in [&branch.leaf, &branch.leaves[1]] {
+				assert!(range.contains(&leaf.name.as_ptr()));
+				assert!(range.contains(&leaf.bytes.as_ptr()));
+			}
+			let Tree::Leaf { leaf: Some(leaf) } = &deserialized[1] else {
+				panic!("unexpected variant");
+			};
+			assert!(range.contains(&leaf.name.as_ptr()));
+		}
+	}
+
+	#[test]
+	fn test_insertion_order() {
+		let mut map: IndexMap<String, u32> = IndexMap::new();
+		for key in ["zulu", "alpha", "mike", "bravo"] {
+			map.insert(String::from(key), key.len() as u32);
+		}
+		for config in [
+			Config::default(),
+			Config {
+				self_describing: true,
+				..Config::new(true)
+			},
+		] {
+			// Entries are written in iteration order
+			let serialized = Serializer::to_bytes_with_config(&map, &config).unwrap();
+			let deserialized: IndexMap<String, u32> =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert!(map.iter().eq(deserialized.iter()));
+		}
+
+		// The canonical settings sort the entries instead
+		let serialized = Serializer::to_bytes_with_config(&map, &Config::canonical()).unwrap();
+		let deserialized: IndexMap<String, u32> =
+			Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(map, deserialized);
+		assert_eq!(
+			deserialized.keys().collect::<Vec<_>>(),
+			vec!["mike", "zulu", "alpha", "bravo"]
+		);
+	}
+
+	#[test]
+	fn test_truncated_number() {
+		let serialized = Serializer::to_bytes(&0x0102030405060708_u64, false).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes::<u64>(&serialized[..5], false),
+			Err(BinaryError::InvalidLength {
+				actual: 5,
+				expected: 8
+			})
+		);
+		// The width is reported for numbers inside other values too
+		let serialized = Serializer::to_bytes(&(0x41_u8, 0x42_u16), true).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes::<(u8, u16)>(&serialized[..3], true),
+			Err(BinaryError::InvalidLength {
+				actual: 1,
+				expected: 2
+			})
+		);
+	}
+
 	// Test Serde Option
 	impl_test_x!(test_none, None::<u64>);
 	impl_test_x!(test_some, Some(0x41));
+	impl_test_x!(test_none_string, None::<String>);
+	impl_test_x!(test_some_string, Some(String::from("test")));
+	impl_test_x!(test_some_vec, Some(vec![0x41_u8, 0x42, 0x43]));
+
+	// Test interior mutability wrappers, which serialize as their contents
+	impl_test_x!(test_refcell, RefCell::new(0x41_u32));
+	impl_test_x!(test_cell, Cell::new(0x41_u8));
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Mutable {
+		pub count: Cell<u8>,
+		pub names: RefCell<Vec<String>>,
+	}
+
+	#[test]
+	fn test_interior_mutability() {
+		let value = Mutable {
+			count: Cell::new(0x41),
+			names: RefCell::new(vec![String::from("a")]),
+		};
+		value.names.borrow_mut().push(String::from("b"));
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, vec![0x02, 0x41, 0x02, 0x01, b'a', 0x01, b'b']);
+		test(value);
+	}
+
+	#[test]
+	fn test_some_empty() {
+		// An empty inner value is the SOME flag followed by a zero length, not the NONE flag
+		let serialized = Serializer::to_bytes(&Some(String::new()), false).unwrap();
+		assert_eq!(serialized, vec![0xFF, 0x00]);
+		test(Some(String::new()));
+
+		let serialized = Serializer::to_bytes(&Some(Vec::<u8>::new()), false).unwrap();
+		assert_eq!(serialized, vec![0xFF, 0x00]);
+		test(Some(Vec::<u8>::new()));
+
+		let serialized = Serializer::to_bytes(&None::<Vec<u8>>, false).unwrap();
+		assert_eq!(serialized, vec![0x00]);
+	}
+
+	#[test]
+	fn test_some_flag_values() {
+		// The contents follow the flag, so values equal to either flag can't be mistaken for it
+		let cases = [
+			(Some(0x00_u8), vec![0xFF, 0x00]),
+			(Some(0xFF_u8), vec![0xFF, 0xFF]),
+			(None, vec![0x00]),
+		];
+		let bitmap = Config {
+			bitmap_options: true,
+			..Config::default()
+		};
+		let self_describing = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		for (value, expected) in cases {
+			assert_eq!(Serializer::to_bytes(&value, false).unwrap(), expected);
+			test(value);
+
+			let value = Sparse {
+				a: value,
+				b: Some(0xFF),
+				c: Some(0x00),
+				..Sparse::default()
+			};
+			let serialized = Serializer::to_bytes_with_config(&value, &bitmap).unwrap();
+			let deserialized: Sparse =
+				Deserializer::from_bytes_with_config(&serialized, &bitmap).unwrap();
+			assert_eq!(value, deserialized);
+
+			let serialized = Serializer::to_bytes_with_config(&value.a, &self_describing).unwrap();
+			let deserialized: Option<u8> =
+				Deserializer::from_bytes_with_config(&serialized, &self_describing).unwrap();
+			assert_eq!(value.a, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_invalid_option_flag() {
+		assert_eq!(
+			Deserializer::from_bytes::<Option<u8>>(&[0x7F, 0x41], false),
+			Err(BinaryError::InvalidOptionFlag { actual: 0x7F })
+		);
+		// The flag of the first field of a struct
+		assert_eq!(
+			Deserializer::from_bytes::<Sparse>(&[0x0A, 0x01, 0x41], false),
+			Err(BinaryError::InvalidOptionFlag { actual: 0x01 })
+		);
+	}
 
 	// Test Serde Units
 	#[test]
@@ -107,11 +783,108 @@ mod tests {
 		assert_eq!(value, deserialized,);
 	}
 
+	#[test]
+	fn test_empty_struct() {
+		// A struct without fields is written as a zero field count, unlike a unit struct
+		assert_eq!(Serializer::to_bytes(&Empty {}, false).unwrap(), vec![0x00]);
+		assert!(Serializer::to_bytes(&Unit, false).unwrap().is_empty());
+		assert_eq!(
+			Serializer::to_bytes(&Single { byte: 0x41 }, false).unwrap(),
+			vec![0x01, 0x41]
+		);
+		for config in [
+			Config::default(),
+			Config {
+				bitmap_options: true,
+				..Config::default()
+			},
+			Config {
+				fingerprint: true,
+				..Config::default()
+			},
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+		] {
+			let serialized = Serializer::to_bytes_with_config(&Empty {}, &config).unwrap();
+			let deserialized: Empty =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(Empty {}, deserialized);
+			let value = Single { byte: 0x41 };
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let deserialized: Single =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+			// A field count that does not match is rejected, whereas self-describing mode skips
+			// the fields the struct does not have
+			assert_eq!(
+				Deserializer::from_bytes_with_config::<Empty>(&serialized, &config).is_ok(),
+				config.self_describing
+			);
+		}
+		test(vec![Empty {}, Empty {}]);
+	}
+
+	#[test]
+	fn test_phantom_data() {
+		assert!(
+			Serializer::to_bytes(&PhantomData::<String>, false)
+				.unwrap()
+				.is_empty()
+		);
+
+		let value = Phantom {
+			byte: 0x41,
+			marker: PhantomData,
+			string: String::from("test"),
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, vec![0x03, 0x41, 0x04, b't', b'e', b's', b't']);
+		test(value);
+	}
+
 	// Test Serde Variants
 	impl_test_x!(test_unit_variant, TestEnum::UnitVariant);
 	impl_test_x!(test_newtype_variant, TestEnum::NewTypeVariant(0x41));
 	impl_test_x!(test_tuple_variant, TestEnum::TupleVariant(0x41, 0x42, 0x43));
 
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Extended {
+		A,
+		B,
+		C,
+		D,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Known {
+		A,
+		B,
+		#[serde(other)]
+		Unknown,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Closed {
+		A,
+		B,
+	}
+
+	#[test]
+	fn test_other_variant() {
+		let serialized = Serializer::to_bytes(&Extended::D, false).unwrap();
+		let deserialized: Known = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, Known::Unknown);
+		let serialized = Serializer::to_bytes(&Extended::B, false).unwrap();
+		let deserialized: Known = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, Known::B);
+
+		// Without a fallback, an unknown index is an error rather than a panic
+		let serialized = Serializer::to_bytes(&Extended::C, false).unwrap();
+		assert!(Deserializer::from_bytes::<Closed>(&serialized, false).is_err());
+	}
+
 	// Test Serde Structs
 	impl_test_x!(
 		test_struct,
@@ -122,6 +895,61 @@ mod tests {
 	);
 	impl_test_x!(test_newtype_struct, NewType(0x41));
 	impl_test_x!(test_tuple_struct, TupleStruct(0x41, 0x42, 0x43));
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct OtherNewType(u8);
+
+	#[test]
+	fn test_newtype_names() {
+		// Newtype structs are written as the value they wrap by default
+		assert_eq!(
+			Serializer::to_bytes(&NewType(0x41), false).unwrap(),
+			Serializer::to_bytes(&0x41_u8, false).unwrap()
+		);
+		assert_eq!(
+			Deserializer::from_bytes::<OtherNewType>(&[0x41], false)
+				.unwrap()
+				.0,
+			0x41
+		);
+
+		let config = Config {
+			newtype_names: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&NewType(0x41), &config).unwrap();
+		assert_eq!(
+			serialized,
+			vec![0x07, b'N', b'e', b'w', b'T', b'y', b'p', b'e', 0x41]
+		);
+		let deserialized: NewType =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, NewType(0x41));
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<OtherNewType>(&serialized, &config),
+			Err(BinaryError::Message {
+				message: String::from("expected newtype struct OtherNewType, found NewType")
+			})
+		);
+
+		let config = Config {
+			self_describing: true,
+			..config
+		};
+		let value = vec![NewType(0x41), NewType(0x42)];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		// The sequence tag and length, then the newtype tag before the name
+		assert_eq!(serialized[2..5], [0x14, 0x10, 0x07]);
+		let deserialized: Vec<NewType> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, value);
+		// The data can still be skipped without knowing its type
+		assert!(
+			Deserializer::from_bytes_with_config::<serde::de::IgnoredAny>(&serialized, &config)
+				.is_ok()
+		);
+	}
+
 	impl_test_x!(
 		test_struct_variant,
 		TestEnum::StructVariant { a: 0x41, b: 0x42 }
@@ -129,9 +957,40 @@ mod tests {
 
 	// Test Serde sequences
 	impl_test_x!(test_vec, vec![0x41, 0x42, 0x43]);
-	impl_test_x!(test_byte_array, [0x41 as u8, 0x42 as u8, 0x43 as u8]);
+	impl_test_x!(test_byte_array, [0x41_u8, 0x42_u8, 0x43_u8]);
 	impl_test_x!(test_array, [0x41, 0x42, 0x43]);
 
+	#[test]
+	fn test_large_arrays() {
+		let value = Arrays {
+			bytes: core::array::from_fn(|i| i as u8),
+			words: core::array::from_fn(|i| (i as u32) << 20),
+			floats: core::array::from_fn(|i| i as f64 / 3.0),
+			longs: core::array::from_fn(|i| (i as u64) << 50),
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Struct length, then each array's length followed by its elements
+		assert_eq!(
+			serialized.len(),
+			1 + (2 + 256) + (1 + 64 * 4) + (1 + 16 * 8) + (2 + 256 * 8)
+		);
+		test(value.clone());
+		test_be(value);
+	}
+
+	#[test]
+	fn test_array_length_mismatch() {
+		let serialized = Serializer::to_bytes(&[0x41_u8; 4], false).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes::<[u8; 3]>(&serialized, false),
+			Err(BinaryError::InvalidLength {
+				actual: 4,
+				expected: 3,
+			})
+		);
+		assert!(Deserializer::from_bytes::<[u8; 4]>(&serialized, false).is_ok());
+	}
+
 	#[test]
 	fn test_map() {
 		let mut v: HashMap<String, char> = HashMap::new();
@@ -141,33 +1000,1961 @@ mod tests {
 		test_be(v.clone());
 	}
 
-	impl_test_x!(test_tuple, ('a', 16, 0x41 as u8));
+	#[test]
+	fn test_unit_map() {
+		// Unit keys and values take no space, the trailing value checks the cursor is still in sync
+		let mut values: HashMap<u8, ()> = HashMap::new();
+		values.insert(0x41, ());
+		values.insert(0x42, ());
+		let serialized = Serializer::to_bytes(&(values.clone(), 0x43_u8), false).unwrap();
+		assert_eq!(serialized.len(), 5);
+		test((values.clone(), 0x43_u8));
+		test_be((values, 0x43_u8));
 
-	fn test<T>(value: T)
-	where
-		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
-	{
-		let serialized = Serializer::to_bytes(&value, false).unwrap();
-		let deserialized: T = Deserializer::from_bytes(&serialized, false).unwrap();
-		assert_eq!(value, deserialized,);
+		let mut keys: BTreeMap<(), u32> = BTreeMap::new();
+		keys.insert((), 0x41);
+		let serialized = Serializer::to_bytes(&(keys.clone(), 0x43_u8), false).unwrap();
+		assert_eq!(serialized, vec![0x02, 0x01, 0x41, 0x00, 0x00, 0x00, 0x43]);
+		test((keys.clone(), 0x43_u8));
+		test_be((keys, 0x43_u8));
+		test((BTreeMap::<(), u32>::new(), 0x43_u8));
 	}
 
-	fn test_be<T>(value: T)
-	where
-		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
-	{
-		let serialized = Serializer::to_bytes(&value, true).unwrap();
-		let deserialized: T = Deserializer::from_bytes(&serialized, true).unwrap();
-		assert_eq!(value, deserialized,);
+	impl_test_x!(test_tuple, ('a', 16, 0x41_u8));
+
+	// Test references and slices at the top level
+	#[test]
+	fn test_references() {
+		assert_eq!(
+			Serializer::to_bytes(&&5_u32, false).unwrap(),
+			Serializer::to_bytes(&5_u32, false).unwrap()
+		);
+		assert_eq!(
+			Serializer::to_bytes(&[1_u8, 2, 3][..], false).unwrap(),
+			Serializer::to_bytes(&vec![1_u8, 2, 3], false).unwrap()
+		);
+		assert_eq!(
+			Serializer::to_bytes(&"hello", false).unwrap(),
+			Serializer::to_bytes(&String::from("hello"), false).unwrap()
+		);
 	}
 
-	fn test_undersized<T>(value: T)
-	where
-		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
-	{
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+	struct Sparse {
+		pub a: Option<u8>,
+		pub b: Option<u8>,
+		pub c: Option<u8>,
+		pub d: Option<u8>,
+		pub e: Option<u8>,
+		pub f: Option<u8>,
+		pub g: Option<u8>,
+		pub h: Option<u8>,
+		pub i: Option<u8>,
+		pub j: Option<u8>,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	struct Nested {
+		pub byte: u8,
+		pub sparse: Option<Sparse>,
+		pub nested: Option<Option<u8>>,
+		pub values: Vec<Option<u8>>,
+	}
+
+	// Test presence bitmaps
+	#[test]
+	fn test_bitmap_options() {
+		let config = Config {
+			bitmap_options: true,
+			..Config::default()
+		};
+		let value = Sparse {
+			b: Some(0x41),
+			j: Some(0x00),
+			..Sparse::default()
+		};
+		let plain = Serializer::to_bytes(&value, false).unwrap();
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(plain.len(), 13);
+		// Field count, two bitmap bytes and the two present values
+		assert_eq!(serialized, vec![0x0A, 0x02, 0x02, 0x41, 0x00]);
+		let deserialized: Sparse =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		let config = Config {
+			bitmap_options: true,
+			..Config::new(true)
+		};
+		let value = Nested {
+			byte: 0x41,
+			sparse: Some(value),
+			nested: Some(None),
+			values: vec![Some(0x41), None],
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Nested =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	// Test types that have a compact, non human readable form
+	#[test]
+	fn test_uuid() {
+		let value = Uuid::from_u128(0x0102_0304_0506_0708_090A_0B0C_0D0E_0F10);
 		let serialized = Serializer::to_bytes(&value, false).unwrap();
-		let shrunk = serialized[0..serialized.len() - 1].to_vec();
+		let mut expected = vec![0x10];
+		expected.extend_from_slice(value.as_bytes());
+		assert_eq!(serialized, expected);
+		let deserialized: Uuid = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
 
-		assert!(Deserializer::from_bytes::<T>(&shrunk, false).is_err());
+		// Struct fields keep the compact form when written with a presence bitmap
+		let config = Config {
+			bitmap_options: true,
+			..Config::default()
+		};
+		let value = UuidField {
+			id: value,
+			option: Some(0x41),
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		// The field count, the bitmap, the Uuid as 16 bytes and the option value
+		let id = Serializer::to_bytes(&value.id, false).unwrap();
+		assert_eq!(serialized[2..serialized.len() - 1], id);
+		assert_eq!(serialized.len(), id.len() + 3);
+		let deserialized: UuidField =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct UuidField {
+		pub id: Uuid,
+		pub option: Option<u8>,
+	}
+
+	// Test paths, which serde writes as strings
+	#[test]
+	fn test_path() {
+		let value = PathBuf::from("/usr/local/bin");
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(
+			serialized,
+			Serializer::to_bytes("/usr/local/bin", false).unwrap()
+		);
+		test(value);
+		test(PathBuf::new());
+	}
+
+	// Test adapters that write values as strings
+	#[serde_as]
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Displayed {
+		#[serde_as(as = "DisplayFromStr")]
+		pub number: u32,
+		#[serde_as(as = "Option<DisplayFromStr>")]
+		pub optional: Option<i64>,
+	}
+
+	#[test]
+	fn test_display_from_str() {
+		let value = Displayed {
+			number: 1234,
+			optional: Some(-5),
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(
+			serialized,
+			vec![0x02, 0x04, b'1', b'2', b'3', b'4', 0xFF, 0x02, b'-', b'5']
+		);
+		test(value);
+
+		// A string that does not parse is an error, not a panic
+		let serialized = vec![0x02, 0x02, b'1', b'x', 0x00];
+		assert!(Deserializer::from_bytes::<Displayed>(&serialized, false).is_err());
+	}
+
+	// Test length and NUL terminated strings
+	#[test]
+	fn test_null_terminated_strings() {
+		let config = Config {
+			string_type: StringType::SizeTaggedAndNullTerminated,
+			..Config::default()
+		};
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(
+			serialized,
+			vec![0x02, 0x41, 0x04, b't', b'e', b's', b't', 0x00]
+		);
+		let deserialized: Test =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		let value = vec![String::new(), String::from("a")];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(serialized, vec![0x02, 0x00, 0x00, 0x01, b'a', 0x00]);
+		let deserialized: Vec<String> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_missing_terminator() {
+		let config = Config {
+			string_type: StringType::SizeTaggedAndNullTerminated,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes(&String::from("test"), false).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<String>(&serialized, &config),
+			Err(BinaryError::UnexpectedEndOfInput)
+		);
+		let serialized = vec![0x04, b't', b'e', b's', b't', 0x41];
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<String>(&serialized, &config),
+			Err(BinaryError::MissingOrInvalidFlag {
+				actual: 0x41,
+				expected: 0x00
+			})
+		);
+	}
+
+	// Test UTF-16 strings
+	#[test]
+	fn test_utf16_strings() {
+		let config = Config {
+			string_type: StringType::Utf16,
+			..Config::default()
+		};
+		// U+1F636 is written as a surrogate pair
+		let serialized = vec![0x02, 0x3D, 0xD8, 0x36, 0xDE];
+		let deserialized: String =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, "\u{1F636}");
+		assert_eq!(
+			Serializer::to_bytes_with_config(&deserialized, &config).unwrap(),
+			serialized
+		);
+		let config_be = Config {
+			string_type: StringType::Utf16,
+			..Config::new(true)
+		};
+		assert_eq!(
+			Serializer::to_bytes_with_config(&deserialized, &config_be).unwrap(),
+			vec![0x02, 0xD8, 0x3D, 0xDE, 0x36]
+		);
+
+		for config in [config.clone(), config_be] {
+			let value = Test {
+				byte: 0x41,
+				string: String::from("t\u{E9}st \u{1F636}"),
+			};
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let deserialized: Test =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+			let sd = Config {
+				self_describing: true,
+				..config
+			};
+			let serialized = Serializer::to_bytes_with_config(&TestEnum::UnitVariant, &sd).unwrap();
+			let deserialized: TestEnum =
+				Deserializer::from_bytes_with_config(&serialized, &sd).unwrap();
+			assert_eq!(deserialized, TestEnum::UnitVariant);
+		}
+
+		// Truncated in the middle of the surrogate pair
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<String>(&[0x02, 0x3D, 0xD8, 0x36], &config),
+			Err(BinaryError::UnexpectedEndOfInput)
+		);
+		// A high surrogate without the low surrogate
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<String>(
+				&[0x02, 0x3D, 0xD8, 0x41, 0x00],
+				&config
+			),
+			Err(BinaryError::InvalidBytes)
+		);
+	}
+
+	// Test interned strings
+	#[test]
+	fn test_framed_map_entries() {
+		let config = Config {
+			framed_map_entries: true,
+			..Config::default()
+		};
+		let mut map: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+		map.insert(0x01, vec![0x0A, 0x0B, 0x0C]);
+		map.insert(0x02, vec![0x14, 0x15, 0x16]);
+		map.insert(0x03, vec![0x1E]);
+		let serialized = Serializer::to_bytes_with_config(&map, &config).unwrap();
+		// Map length, then the length of each entry before its key and value
+		assert_eq!(
+			serialized,
+			vec![
+				0x03, 0x05, 0x01, 0x03, 0x0A, 0x0B, 0x0C, 0x05, 0x02, 0x03, 0x14, 0x15, 0x16, 0x03,
+				0x03, 0x01, 0x1E
+			]
+		);
+		let deserialized: BTreeMap<u8, Vec<u8>> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(map, deserialized);
+
+		// A value in the middle that reads short shifts every entry after it when unframed, so
+		// its last byte is read as a key and the error only shows at the end of the data
+		let mut unframed = Serializer::to_bytes(&map, false).unwrap();
+		unframed[7] = 0x02;
+		assert_eq!(
+			Deserializer::from_bytes::<BTreeMap<u8, Vec<u8>>>(&unframed, false),
+			Err(BinaryError::UnexpectedEndOfInput)
+		);
+
+		// The same corruption of framed data is caught at the entry
+		let mut framed = serialized;
+		framed[9] = 0x02;
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<BTreeMap<u8, Vec<u8>>>(&framed, &config),
+			Err(BinaryError::InvalidLength {
+				actual: 4,
+				expected: 5
+			})
+		);
+
+		// Nested maps are framed too, and canonical sorting keeps each length with its entry
+		let mut nested: HashMap<String, BTreeMap<u16, String>> = HashMap::new();
+		for i in 0..4_u16 {
+			let inner = (0..i).map(|j| (j, format!("{}", j))).collect();
+			nested.insert(format!("key {}", i), inner);
+		}
+		for config in [
+			config.clone(),
+			Config {
+				canonical: true,
+				..config
+			},
+		] {
+			let serialized = Serializer::to_bytes_with_config(&nested, &config).unwrap();
+			let deserialized: HashMap<String, BTreeMap<u16, String>> =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(nested, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_intern_strings() {
+		let config = Config {
+			intern_strings: true,
+			..Config::default()
+		};
+		let value: Vec<String> = ["info", "warning", "info", "info", "warning", "error"]
+			.iter()
+			.map(|s| String::from(*s))
+			.collect();
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let mut expected = vec![0x06, 0x00, 0x04];
+		expected.extend(b"info");
+		expected.extend([0x00, 0x07]);
+		expected.extend(b"warning");
+		expected.extend([0x01, 0x01, 0x02, 0x00, 0x05]);
+		expected.extend(b"error");
+		assert_eq!(serialized, expected);
+		assert!(serialized.len() < Serializer::to_bytes(&value, false).unwrap().len());
+		let deserialized: Vec<&str> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		// Repeats are borrowed from the first occurrence
+		assert!(std::ptr::eq(deserialized[0], deserialized[2]));
+
+		// Field names and variant names are interned in self-describing mode
+		let records: Vec<Named> = (0..10)
+			.map(|i| Named {
+				name: String::from("record"),
+				value: Some(i),
+			})
+			.collect();
+		let sd = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let sd_interned = Config {
+			intern_strings: true,
+			..sd.clone()
+		};
+		let serialized = Serializer::to_bytes_with_config(&records, &sd_interned).unwrap();
+		let plain = Serializer::to_bytes_with_config(&records, &sd).unwrap();
+		assert!(serialized.len() < plain.len());
+		let deserialized: Vec<Named> =
+			Deserializer::from_bytes_with_config(&serialized, &sd_interned).unwrap();
+		assert_eq!(records, deserialized);
+		for config in [
+			sd_interned,
+			Config {
+				string_type: StringType::Utf16,
+				..config.clone()
+			},
+		] {
+			let value = (TestEnum::UnitVariant, TestEnum::UnitVariant, value.clone());
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let deserialized: (TestEnum, TestEnum, Vec<String>) =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+		}
+
+		// A reference to a string that has not been read yet
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<Vec<String>>(&[0x01, 0x01], &config),
+			Err(BinaryError::InvalidBytes)
+		);
+		let canonical = Config {
+			intern_strings: true,
+			..Config::canonical()
+		};
+		assert!(Serializer::to_bytes_with_config(&value, &canonical).is_err());
+	}
+
+	// Test schema fingerprints
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Renamed {
+		code: u8,
+		name: String,
+	}
+
+	#[test]
+	fn test_fingerprint() {
+		let config = Config {
+			fingerprint: true,
+			..Config::default()
+		};
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(serialized.len(), 8 + 7);
+		let deserialized: Test =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		// The same layout with different field names is rejected
+		assert!(Deserializer::from_bytes::<Renamed>(&serialized[8..], false).is_ok());
+		assert!(matches!(
+			Deserializer::from_bytes_with_config::<Renamed>(&serialized, &config),
+			Err(BinaryError::SchemaMismatch { .. })
+		));
+
+		let serialized = Serializer::to_bytes_with_config(&vec![0x41_u8], &config).unwrap();
+		let deserialized: Vec<u8> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, vec![0x41_u8]);
+		assert!(Deserializer::from_bytes_with_config::<Test>(&serialized, &config).is_err());
+	}
+
+	#[test]
+	fn test_struct_header() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		// No flag is written before a struct; the fingerprint and the field names stand in for
+		// its name, and the field count always follows them
+		for fingerprint in [false, true] {
+			for self_describing in [false, true] {
+				let config = Config {
+					fingerprint,
+					self_describing,
+					..Config::default()
+				};
+				let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+				let start = if fingerprint { 8 } else { 0 };
+				let header: &[u8] = if self_describing {
+					&[0x13, 0x02]
+				} else {
+					&[0x02]
+				};
+				assert_eq!(&serialized[start..start + header.len()], header);
+				let deserialized: Test =
+					Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+				assert_eq!(value, deserialized);
+			}
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Triple(u8, u8, u8);
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Wrapper(Test, u8);
+
+	#[test]
+	fn test_tuple_struct_fingerprint() {
+		let config = Config {
+			fingerprint: true,
+			..Config::default()
+		};
+		let value = TupleStruct(0x41, 0x42, 0x43);
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: TupleStruct =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		// The same layout under a different name is rejected
+		assert!(Deserializer::from_bytes::<Triple>(&serialized[8..], false).is_ok());
+		assert!(matches!(
+			Deserializer::from_bytes_with_config::<Triple>(&serialized, &config),
+			Err(BinaryError::SchemaMismatch { .. })
+		));
+		assert!(Deserializer::from_bytes_with_config::<Vec<u8>>(&serialized, &config).is_err());
+
+		// The names of the fields of a struct inside it are not part of its fingerprint
+		let value = Wrapper(
+			Test {
+				byte: 0x41,
+				string: String::from("test"),
+			},
+			0x42,
+		);
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Wrapper =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	// Test the byte order of compressed lengths
+	#[test]
+	fn test_varint_endianness() {
+		let value = vec![0x41_u8; 0x1234];
+		let serialized = Serializer::to_bytes(&value, true).unwrap();
+		assert_eq!(serialized[..3], [0xB4, 0x24, 0x01]);
+
+		let config = Config {
+			varint_endianness: ByteFormat::BigEndian,
+			..Config::new(true)
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(serialized[..3], [0x80, 0x32, 0x34]);
+		assert_eq!(serialized.len(), 3 + 0x1234);
+		let deserialized: Vec<u8> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	// Test the total allocation limit
+	#[test]
+	fn test_max_total_alloc() {
+		let value: Vec<Vec<String>> = vec![vec![String::from("test"); 4]; 4];
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// 4 + 4 * 4 entries and 16 * 4 bytes of strings
+		let config = Config {
+			max_total_alloc: Some(84),
+			..Config::default()
+		};
+		let deserialized: Vec<Vec<String>> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		let config = Config {
+			max_total_alloc: Some(83),
+			..Config::default()
+		};
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<Vec<Vec<String>>>(&serialized, &config),
+			Err(BinaryError::LengthLimitExceeded {
+				actual: 84,
+				limit: 83
+			})
+		);
+
+		// A single huge declared length is rejected before anything is read
+		let config = Config {
+			max_total_alloc: Some(1024),
+			..Config::default()
+		};
+		let mut serialized = vec![0x01];
+		serialized.extend_from_slice(&compress_usize(usize::MAX));
+		assert!(matches!(
+			Deserializer::from_bytes_with_config::<Vec<Vec<u8>>>(&serialized, &config),
+			Err(BinaryError::LengthLimitExceeded { limit: 1024, .. })
+		));
+	}
+
+	#[test]
+	fn test_max_depth() {
+		let config = Config {
+			self_describing: true,
+			max_depth: Some(64),
+			..Config::default()
+		};
+		// Sequences of one element, each inside the one before, and an empty one at the bottom
+		let nested = |depth: usize| {
+			let mut serialized = [0x12, 0x01].repeat(depth - 1);
+			serialized.extend_from_slice(&[0x12, 0x00]);
+			serialized
+		};
+		let deserialized: serde_json::Value =
+			Deserializer::from_bytes_with_config(&nested(64), &config).unwrap();
+		assert!(deserialized.is_array());
+
+		// Deep enough to overflow the stack without the limit
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<serde_json::Value>(&nested(1_000_000), &config),
+			Err(BinaryError::DepthLimitExceeded { limit: 64 })
+		);
+		assert!(
+			Deserializer::from_bytes_with_config::<serde_json::Value>(&nested(65), &config)
+				.is_err()
+		);
+
+		// Maps with the key "a", and options, count as well
+		let mut serialized = [0x13, 0x01, 0x10, 0x01, b'a'].repeat(64);
+		serialized.extend_from_slice(&[0xFF, 0x01]);
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<serde_json::Value>(&serialized, &config),
+			Err(BinaryError::DepthLimitExceeded { limit: 64 })
+		);
+	}
+
+	// Test self-describing mode
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	#[serde(tag = "type")]
+	enum Internal {
+		Empty,
+		Point { x: i32, y: i32 },
+		Named(Named),
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	struct Named {
+		pub name: String,
+		pub value: Option<u64>,
+	}
+
+	#[test]
+	fn test_self_describing() {
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(
+			serialized,
+			vec![
+				0x13, 0x02, 0x10, 0x04, b'b', b'y', b't', b'e', 0x03, 0x41, 0x10, 0x06, b's', b't',
+				b'r', b'i', b'n', b'g', 0x10, 0x04, b't', b'e', b's', b't'
+			]
+		);
+		test_sd(value);
+
+		test_sd(Unit);
+		test_sd(NewType(0x41));
+		test_sd(TupleStruct(0x41, 0x42, 0x43));
+		test_sd(TestEnum::UnitVariant);
+		test_sd(TestEnum::NewTypeVariant(0x41));
+		test_sd(TestEnum::TupleVariant(0x41, 0x42, 0x43));
+		test_sd(TestEnum::StructVariant { a: 0x41, b: 0x42 });
+		test_sd(('a', -16_i16, 0x41_u128, 1.5_f32, -2.5_f64, true));
+		test_sd(vec![Some(String::from("test")), None]);
+		test_sd(Phantom {
+			byte: 0x41,
+			marker: PhantomData,
+			string: String::new(),
+		});
+		let mut map: HashMap<String, Vec<u8>> = HashMap::new();
+		map.insert(String::from("a"), vec![0x41, 0x42]);
+		map.insert(String::from("b"), vec![]);
+		test_sd(map);
+	}
+
+	#[test]
+	fn test_self_describing_byte_width() {
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&0x0041_u16, &config).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<u8>(&serialized, &config),
+			Err(BinaryError::MissingOrInvalidFlag {
+				actual: 0x04,
+				expected: 0x03
+			})
+		);
+		assert!(Deserializer::from_bytes_with_config::<i8>(&serialized, &config).is_err());
+		// Without tags the first byte is read, as there is nothing to check it against
+		let serialized = Serializer::to_bytes(&0x0041_u16, false).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes::<u8>(&serialized, false).unwrap(),
+			0x41
+		);
+
+		let serialized = Serializer::to_bytes_with_config(&(0x41_u8, -1_i8), &config).unwrap();
+		let deserialized: (u8, i8) =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, (0x41, -1));
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	#[serde(rename = "Shade")]
+	enum OldShade {
+		Light,
+		Tinted(u8),
+		Mixed { red: u8, blue: u8 },
+		Dark,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	#[serde(rename = "Shade")]
+	enum Shade {
+		Light,
+		Unknown,
+	}
+
+	#[test]
+	fn test_variant_fallbacks() {
+		let mut config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let value = vec![
+			OldShade::Tinted(0x41),
+			OldShade::Light,
+			OldShade::Mixed {
+				red: 0x41,
+				blue: 0x42,
+			},
+			OldShade::Dark,
+		];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<Vec<Shade>>(&serialized, &config).is_err());
+
+		config
+			.variant_fallbacks
+			.insert(String::from("Shade"), String::from("Unknown"));
+		let deserialized: Vec<Shade> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(
+			deserialized,
+			vec![Shade::Unknown, Shade::Light, Shade::Unknown, Shade::Unknown]
+		);
+		// Known variants are read as usual
+		let deserialized: Vec<OldShade> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		// Variants are written by index without self-describing mode, so there is no fallback
+		config.self_describing = false;
+		let serialized = Serializer::to_bytes_with_config(&OldShade::Dark, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<Shade>(&serialized, &config).is_err());
+	}
+
+	#[test]
+	fn test_field_orders() {
+		#[derive(Serialize, Deserialize, Debug, PartialEq)]
+		struct Reordered {
+			pub c: String,
+			pub a: u8,
+			pub b: u16,
+		}
+
+		#[derive(Serialize, Deserialize, Debug, PartialEq)]
+		struct Original {
+			pub a: u8,
+			pub b: u16,
+			pub c: String,
+		}
+
+		let value = Original {
+			a: 0x41,
+			b: 0x4243,
+			c: String::from("test"),
+		};
+		let mut config = Config::default();
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<Reordered>(&serialized, &config).is_err());
+
+		config
+			.field_orders
+			.insert(String::from("Reordered"), vec![1, 2, 0]);
+		let deserialized: Reordered =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(
+			deserialized,
+			Reordered {
+				c: String::from("test"),
+				a: 0x41,
+				b: 0x4243,
+			}
+		);
+
+		// The order must cover each field written, and only the fields the struct has
+		config
+			.field_orders
+			.insert(String::from("Reordered"), vec![1, 2]);
+		assert!(Deserializer::from_bytes_with_config::<Reordered>(&serialized, &config).is_err());
+		config
+			.field_orders
+			.insert(String::from("Reordered"), vec![1, 2, 3]);
+		assert!(Deserializer::from_bytes_with_config::<Reordered>(&serialized, &config).is_err());
+	}
+
+	#[test]
+	fn test_float_keys() {
+		let mut map: BTreeMap<OrderedFloat<f64>, u32> = BTreeMap::new();
+		map.insert(OrderedFloat(-1.5), 0x41);
+		map.insert(OrderedFloat(0.0), 0x42);
+		map.insert(OrderedFloat(f64::NAN), 0x43);
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&map, &config).unwrap();
+		// Each key is tagged as an f64 rather than assumed to be a string or integer
+		assert_eq!(serialized[0..3], [0x13, 0x03, 0x0E]);
+		assert_eq!(serialized[3..11], (-1.5_f64).to_le_bytes());
+		test_sd(map);
+
+		let mut map: BTreeMap<OrderedFloat<f32>, Vec<OrderedFloat<f32>>> = BTreeMap::new();
+		map.insert(OrderedFloat(2.5), vec![OrderedFloat(f32::INFINITY)]);
+		map.insert(OrderedFloat(-0.25), vec![]);
+		test_sd(map);
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+	struct SmallStruct {
+		pub id: u16,
+		pub name: String,
+		pub flag: Option<bool>,
+	}
+
+	#[test]
+	fn test_struct_keys() {
+		let mut map: BTreeMap<SmallStruct, u32> = BTreeMap::new();
+		for i in 0..4_u16 {
+			let key = SmallStruct {
+				id: i,
+				name: format!("key {}", i),
+				flag: (i % 2 == 0).then_some(i == 0),
+			};
+			map.insert(key, 0x41 + u32::from(i));
+		}
+		let configs = [
+			Config::default(),
+			Config::new(true),
+			Config::canonical(),
+			Config {
+				bitmap_options: true,
+				intern_strings: true,
+				..Config::default()
+			},
+		];
+		for config in configs {
+			let serialized = Serializer::to_bytes_with_config(&map, &config).unwrap();
+			let deserialized: BTreeMap<SmallStruct, u32> =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(map, deserialized);
+		}
+		test_sd(map.clone());
+
+		// Map length, then the first key with its struct length, and the value right after it
+		let serialized = Serializer::to_bytes(&map, false).unwrap();
+		assert_eq!(
+			serialized[..14],
+			[
+				0x04, 0x03, 0x00, 0x00, 0x05, b'k', b'e', b'y', b' ', b'0', 0xFF, 0x01, 0x41, 0x00
+			]
+		);
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Large {
+		pub id: u64,
+		pub name: String,
+		pub tags: Vec<String>,
+		pub nested: Test,
+		pub flag: bool,
+	}
+
+	#[test]
+	fn test_read_field() {
+		let value = Large {
+			id: 0x41,
+			name: String::from("test"),
+			tags: vec![String::from("a"), String::from("b")],
+			nested: Test {
+				byte: 0x42,
+				string: String::from("inner"),
+			},
+			flag: true,
+		};
+		for config in [
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+			Config {
+				self_describing: true,
+				fingerprint: true,
+				intern_strings: true,
+				..Config::new(true)
+			},
+		] {
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			// Only the requested field is deserialized; the others are skipped
+			let name: &str = Deserializer::with_config(&serialized, config.clone())
+				.read_field("name")
+				.unwrap();
+			assert_eq!(name, "test");
+			let flag: bool = Deserializer::with_config(&serialized, config.clone())
+				.read_field("flag")
+				.unwrap();
+			assert!(flag);
+			let nested: Test = Deserializer::with_config(&serialized, config.clone())
+				.read_field("nested")
+				.unwrap();
+			assert_eq!(nested, value.nested);
+			assert!(matches!(
+				Deserializer::with_config(&serialized, config.clone()).read_field::<u8>("missing"),
+				Err(BinaryError::Message { .. })
+			));
+		}
+
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert!(matches!(
+			Deserializer::new(&serialized, false).read_field::<String>("name"),
+			Err(BinaryError::Message { .. })
+		));
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Envelope {
+		pub kind: u8,
+		#[serde(with = "serde_bytes")]
+		pub payload: Vec<u8>,
+		pub checksum: u16,
+	}
+
+	#[test]
+	fn test_sub_deserializer() {
+		let inner = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		for big_endian in [false, true] {
+			let value = Envelope {
+				kind: 0x01,
+				payload: Serializer::to_bytes(&inner, big_endian).unwrap(),
+				checksum: 0x1234,
+			};
+			let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+			let mut deserializer = Deserializer::new(&serialized, big_endian);
+			// The field count, then the fields in order
+			deserializer.expect(0x03_u8).unwrap();
+			deserializer.expect(0x01_u8).unwrap();
+			let mut sub = deserializer.sub_deserializer().unwrap();
+			assert_eq!(Test::deserialize(&mut sub).unwrap(), inner);
+			// The sub-message ends where the payload does
+			assert_eq!(
+				u8::deserialize(&mut sub),
+				Err(BinaryError::UnexpectedEndOfInput)
+			);
+			assert_eq!(u16::deserialize(&mut deserializer).unwrap(), 0x1234);
+		}
+
+		// A length beyond the end of the data
+		let mut deserializer = Deserializer::new(&[0x05, 0x41, 0x42], false);
+		assert!(matches!(
+			deserializer.sub_deserializer(),
+			Err(BinaryError::UnexpectedEndOfInput)
+		));
+
+		// A length too large to add to the position
+		let serialized = compress_usize(usize::MAX);
+		let mut deserializer = Deserializer::new(&serialized, false);
+		assert!(matches!(
+			deserializer.sub_deserializer(),
+			Err(BinaryError::UnexpectedEndOfInput)
+		));
+		assert!(matches!(
+			Deserializer::from_bytes::<String>(&serialized, false),
+			Err(BinaryError::UnexpectedEndOfInput)
+		));
+	}
+
+	#[test]
+	fn test_expect() {
+		const MAGIC: u32 = 0x53424132;
+		let serialized = Serializer::to_bytes(&(MAGIC, 0x41_u8), false).unwrap();
+		// Read the elements one at a time, after the tuple length
+		let mut deserializer = Deserializer::new(&serialized[1..], false);
+		deserializer.expect(MAGIC).unwrap();
+		assert_eq!(u8::deserialize(&mut deserializer).unwrap(), 0x41);
+
+		// The magic number read in the wrong byte order
+		let mut deserializer = Deserializer::new(&serialized[1..], true);
+		assert_eq!(
+			deserializer.expect(MAGIC),
+			Err(BinaryError::Message {
+				message: String::from("expected 1396850994, found 843137619")
+			})
+		);
+	}
+
+	#[test]
+	fn test_detect_endian() {
+		const MAGIC: u32 = 0x53424132;
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		for big_endian in [false, true] {
+			let serialized = Serializer::to_bytes_with_magic(MAGIC, &value, big_endian).unwrap();
+			assert_eq!(
+				serialized.len(),
+				4 + Serializer::to_bytes(&value, false).unwrap().len()
+			);
+			let (deserialized, detected): (Test, bool) =
+				Deserializer::from_bytes_detect_endian(&serialized, MAGIC).unwrap();
+			assert_eq!(value, deserialized);
+			assert_eq!(detected, big_endian);
+		}
+		let serialized = Serializer::to_bytes_with_magic(MAGIC, &0x0102_u16, true).unwrap();
+		let (deserialized, _): (u16, bool) =
+			Deserializer::from_bytes_detect_endian(&serialized, MAGIC).unwrap();
+		assert_eq!(deserialized, 0x0102);
+
+		assert_eq!(
+			Deserializer::from_bytes_detect_endian::<u16>(&serialized, 0x53424133),
+			Err(BinaryError::Message {
+				message: String::from(
+					"expected the magic number 0x53424133 in either byte order, found 0x32414253"
+				)
+			})
+		);
+		assert!(Deserializer::from_bytes_detect_endian::<u16>(&serialized[..3], MAGIC).is_err());
+	}
+
+	#[test]
+	fn test_seq_iter() {
+		let value: Vec<u64> = (0..1000).collect();
+		let configs = [
+			Config::default(),
+			Config::new(true),
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+			Config {
+				validate_element_size: true,
+				..Config::default()
+			},
+		];
+		for config in configs {
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let mut deserializer = Deserializer::with_config(&serialized, config);
+			let mut sum = 0;
+			for element in deserializer.deserialize_seq_iter::<u64>().unwrap() {
+				sum += element.unwrap();
+			}
+			assert_eq!(sum, 499_500);
+		}
+
+		// The iterator ends after the element that could not be read
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let mut deserializer = Deserializer::new(&serialized[..20], false);
+		let elements: Vec<crate::Result<u64>> =
+			deserializer.deserialize_seq_iter().unwrap().collect();
+		assert_eq!(elements.len(), 3);
+		assert_eq!(elements[1], Ok(1));
+		assert!(elements[2].is_err());
+	}
+
+	#[test]
+	fn test_coercion() {
+		// Without type tags a number is read in the width of the type it is read as
+		let serialized = Serializer::to_bytes(&0x41_u32, false).unwrap();
+		assert_eq!(
+			Deserializer::from_bytes::<i64>(&serialized, false),
+			Err(BinaryError::InvalidLength {
+				actual: 4,
+				expected: 8
+			})
+		);
+		let serialized = Serializer::to_bytes(&(0x41_u32, 0x42_u32), false).unwrap();
+		// Two `u32` values are misread as a single `i64`, after the tuple length
+		assert_eq!(
+			Deserializer::from_bytes::<i64>(&serialized[1..], false).unwrap(),
+			0x0000_0042_0000_0041
+		);
+
+		// With type tags a number can be read as any type that holds its value
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&0x41_u32, &config).unwrap();
+		let deserialized: i64 = Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, 0x41);
+		let deserialized: u128 =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, 0x41);
+		let deserialized: i16 = Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, 0x41);
+		let serialized = Serializer::to_bytes_with_config(&u32::MAX, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<i16>(&serialized, &config).is_err());
+		let serialized = Serializer::to_bytes_with_config(&-1_i32, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<u64>(&serialized, &config).is_err());
+		let deserialized: i128 =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, -1);
+
+		// A `u8` must have been written as a `u8`
+		let serialized = Serializer::to_bytes_with_config(&0x41_u32, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<u8>(&serialized, &config).is_err());
+		// Integers can be read as floats, but floats are not read as integers
+		let deserialized: f64 = Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, 65.0);
+		let serialized = Serializer::to_bytes_with_config(&1.0_f32, &config).unwrap();
+		assert!(Deserializer::from_bytes_with_config::<u32>(&serialized, &config).is_err());
+		let deserialized: f64 = Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, 1.0);
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct RecordV1 {
+		pub id: u32,
+		pub name: String,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct RecordV2 {
+		pub id: u32,
+		pub email: Option<String>,
+		pub name: String,
+		pub tags: Vec<Test>,
+	}
+
+	#[test]
+	fn test_extra_fields() {
+		let value = RecordV2 {
+			id: 0x41,
+			email: Some(String::from("test@example.com")),
+			name: String::from("test"),
+			tags: vec![Test {
+				byte: 0x42,
+				string: String::from("tag"),
+			}],
+		};
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&vec![&value, &value], &config).unwrap();
+		let deserialized: Vec<RecordV1> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		let expected = || RecordV1 {
+			id: 0x41,
+			name: String::from("test"),
+		};
+		// The second record is read from the right place after skipping the first one's extras
+		assert_eq!(deserialized, vec![expected(), expected()]);
+
+		// Positional data with extra fields is rejected rather than misread
+		for config in [
+			Config::default(),
+			Config {
+				bitmap_options: true,
+				..Config::default()
+			},
+		] {
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			assert_eq!(
+				Deserializer::from_bytes_with_config::<RecordV1>(&serialized, &config),
+				Err(BinaryError::InvalidLength {
+					actual: 4,
+					expected: 2
+				})
+			);
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Contact {
+		pub name: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub email: Option<String>,
+		pub age: u8,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct FullContact {
+		pub name: String,
+		pub email: Option<String>,
+		pub age: u8,
+	}
+
+	#[test]
+	fn test_skipped_fields() {
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let value = Contact {
+			name: String::from("test"),
+			email: None,
+			age: 0x41,
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let full = FullContact {
+			name: String::from("test"),
+			email: None,
+			age: 0x41,
+		};
+		let full_serialized = Serializer::to_bytes_with_config(&full, &config).unwrap();
+		// The key and the NONE flag of the skipped field are left out
+		assert_eq!(serialized.len() + 8, full_serialized.len());
+		let deserialized: Contact =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		// A skipped field is absent, so a reader without the attribute still gets the default
+		let deserialized: FullContact =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(full, deserialized);
+
+		let value = Contact {
+			email: Some(String::from("test@example.com")),
+			..value
+		};
+		test_sd(value);
+	}
+
+	// Declares all its fields, then skips the last one when it is `None`, as some hand written
+	// implementations do
+	struct SkippingLast {
+		pub values: [u8; 8],
+		pub last: Option<u8>,
+	}
+
+	impl Serialize for SkippingLast {
+		fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+		where
+			S: serde::Serializer,
+		{
+			use serde::ser::SerializeStruct;
+
+			const NAMES: [&str; 9] = ["f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8"];
+			let mut state = serializer.serialize_struct("Nine", 9)?;
+			for (name, value) in NAMES.iter().zip(self.values) {
+				state.serialize_field(name, &value)?;
+			}
+			match self.last {
+				Some(_) => state.serialize_field(NAMES[8], &self.last)?,
+				None => state.skip_field(NAMES[8])?,
+			}
+			state.end()
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Nine {
+		pub f0: u8,
+		pub f1: u8,
+		pub f2: u8,
+		pub f3: u8,
+		pub f4: u8,
+		pub f5: u8,
+		pub f6: u8,
+		pub f7: u8,
+		#[serde(default)]
+		pub f8: Option<u8>,
+	}
+
+	#[test]
+	fn test_skip_field() {
+		let value = |last| SkippingLast {
+			values: [0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48],
+			last,
+		};
+		let nine = |f8| Nine {
+			f0: 0x41,
+			f1: 0x42,
+			f2: 0x43,
+			f3: 0x44,
+			f4: 0x45,
+			f5: 0x46,
+			f6: 0x47,
+			f7: 0x48,
+			f8,
+		};
+		let serialized = Serializer::to_bytes(&value(None), false).unwrap();
+		// The length counts only the fields written
+		assert_eq!(serialized[0], 0x08);
+		assert_eq!(serialized.len(), 1 + 8);
+		assert_eq!(
+			Serializer::to_bytes(&value(Some(0x49)), false).unwrap()[0],
+			0x09
+		);
+
+		let bitmap = Config {
+			bitmap_options: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value(None), &bitmap).unwrap();
+		// The bitmap shrinks to one byte, for the eight fields written
+		assert_eq!(serialized[..2], [0x08, 0xFF]);
+		assert_eq!(serialized.len(), 1 + 1 + 8);
+		for config in [
+			Config::default(),
+			bitmap,
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+		] {
+			for last in [None, Some(0x49)] {
+				let serialized = Serializer::to_bytes_with_config(&value(last), &config).unwrap();
+				let deserialized: Nine =
+					Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+				assert_eq!(deserialized, nine(last));
+			}
+		}
+	}
+
+	#[test]
+	fn test_internally_tagged() {
+		for value in [
+			Internal::Empty,
+			Internal::Point { x: 1, y: -1 },
+			Internal::Named(Named {
+				name: String::from("test"),
+				value: Some(0x41),
+			}),
+		] {
+			test_sd(value.clone());
+			// Positional data cannot be read without knowing the variant first
+			let serialized = Serializer::to_bytes(&value, false).unwrap();
+			assert!(matches!(
+				Deserializer::from_bytes::<Internal>(&serialized, false),
+				Err(BinaryError::Message { .. })
+			));
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	#[serde(tag = "t", content = "c")]
+	enum Adjacent {
+		Empty,
+		Value(u32),
+		Pair(u8, String),
+		Point { x: i32, y: i32 },
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	#[serde(untagged)]
+	enum Untagged {
+		Flag(bool),
+		Number(u64),
+		Text(String),
+		Named(Named),
+		List(Vec<Untagged>),
+	}
+
+	#[test]
+	fn test_adjacently_tagged() {
+		for value in [
+			Adjacent::Empty,
+			Adjacent::Value(0x41),
+			Adjacent::Pair(0x41, String::from("test")),
+			Adjacent::Point { x: 1, y: -1 },
+		] {
+			test_sd(value.clone());
+			// The tag is written as a unit variant but read back as a bare variant index
+			let serialized = Serializer::to_bytes(&value, false).unwrap();
+			assert!(Deserializer::from_bytes::<Adjacent>(&serialized, false).is_err());
+		}
+	}
+
+	#[test]
+	fn test_untagged() {
+		for value in [
+			Untagged::Flag(true),
+			Untagged::Number(0x41),
+			Untagged::Text(String::from("test")),
+			Untagged::Named(Named {
+				name: String::from("test"),
+				value: None,
+			}),
+			Untagged::List(vec![Untagged::Number(1), Untagged::Text(String::new())]),
+		] {
+			test_sd(value.clone());
+			let serialized = Serializer::to_bytes(&value, false).unwrap();
+			match Deserializer::from_bytes::<Untagged>(&serialized, false) {
+				Err(BinaryError::Message { message }) => {
+					assert!(message.contains("self-describing"))
+				}
+				other => panic!("unexpected result {:?}", other),
+			}
+		}
+	}
+
+	#[test]
+	fn test_ignored_any() {
+		let serialized = Serializer::to_bytes(&0x41_u8, false).unwrap();
+		assert!(matches!(
+			Deserializer::from_bytes::<serde::de::IgnoredAny>(&serialized, false),
+			Err(BinaryError::Message { .. })
+		));
+
+		// Any complete self-describing value can be checked without knowing its type
+		let mut map: BTreeMap<String, Option<Vec<i16>>> = BTreeMap::new();
+		map.insert(String::from("a"), Some(vec![-1, 0x41]));
+		map.insert(String::from("b"), None);
+		for config in [
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+			Config {
+				self_describing: true,
+				intern_strings: true,
+				newtype_names: true,
+				..Config::new(true)
+			},
+		] {
+			let values = [
+				Serializer::to_bytes_with_config(&map, &config).unwrap(),
+				Serializer::to_bytes_with_config(
+					&Test {
+						byte: 0x41,
+						string: String::from("test"),
+					},
+					&config,
+				)
+				.unwrap(),
+				Serializer::to_bytes_with_config(
+					&(
+						TestEnum::StructVariant { a: 0x41, b: 0x42 },
+						TestEnum::UnitVariant,
+						NewType(0x41),
+						'a',
+						1.5_f64,
+						0x41_u128,
+						(),
+					),
+					&config,
+				)
+				.unwrap(),
+			];
+			for serialized in values {
+				assert!(
+					Deserializer::from_bytes_with_config::<serde::de::IgnoredAny>(
+						&serialized,
+						&config
+					)
+					.is_ok()
+				);
+				// Every truncated buffer is rejected
+				for len in 0..serialized.len() {
+					assert!(
+						Deserializer::from_bytes_with_config::<serde::de::IgnoredAny>(
+							&serialized[..len],
+							&config
+						)
+						.is_err()
+					);
+				}
+				// As is an unknown type tag
+				let mut corrupt = serialized.clone();
+				corrupt[0] = 0xEE;
+				assert!(
+					Deserializer::from_bytes_with_config::<serde::de::IgnoredAny>(
+						&corrupt, &config
+					)
+					.is_err()
+				);
+			}
+		}
+	}
+
+	// Test length prefixed frames
+	#[test]
+	fn test_frame() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		let frame = Serializer::to_frame(&value, false).unwrap();
+		assert_eq!(frame, vec![0x07, 0x02, 0x41, 0x04, b't', b'e', b's', b't']);
+		let deserialized: Test = Deserializer::from_frame(&frame, false, true).unwrap();
+		assert_eq!(value, deserialized);
+
+		let mut padded = frame.clone();
+		padded.push(0x00);
+		assert_eq!(
+			Deserializer::from_frame::<Test>(&padded, false, true),
+			Err(BinaryError::InvalidLength {
+				actual: 8,
+				expected: 7,
+			})
+		);
+		let deserialized: Test = Deserializer::from_frame(&padded, false, false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_frame_unit() {
+		let frame = Serializer::to_frame(&(), false).unwrap();
+		assert_eq!(frame, vec![0x00]);
+		let deserialized: () = Deserializer::from_frame(&frame, false, true).unwrap();
+		assert_eq!(deserialized, ());
+
+		// A unit reads no bytes, so without a frame a corrupted value goes unnoticed
+		assert!(Deserializer::from_bytes::<()>(&[0x05], false).is_ok());
+		assert_eq!(
+			Deserializer::from_frame::<()>(&[0x01, 0x05], false, true),
+			Err(BinaryError::InvalidLength {
+				actual: 0,
+				expected: 1,
+			})
+		);
+		assert!(Deserializer::from_frame::<()>(&[0x01, 0x05], false, false).is_ok());
+
+		// Any other value that leaves part of the frame unread
+		let mut frame = Serializer::to_frame(&0x41_u16, false).unwrap();
+		frame[0] = 0x03;
+		frame.push(0x00);
+		assert_eq!(
+			Deserializer::from_frame::<u16>(&frame, false, true),
+			Err(BinaryError::InvalidLength {
+				actual: 2,
+				expected: 3,
+			})
+		);
+	}
+
+	#[test]
+	fn test_frame_truncated() {
+		let mut frame = Serializer::to_frame(&0x41_u32, false).unwrap();
+		frame[0] = 0x10;
+		assert_eq!(
+			Deserializer::from_frame::<u32>(&frame, false, false),
+			Err(BinaryError::InvalidLength {
+				actual: 4,
+				expected: 16,
+			})
+		);
+		assert!(Deserializer::from_frame::<u32>(&[], false, false).is_err());
+	}
+
+	fn test<T>(value: T)
+	where
+		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
+	{
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let deserialized: T = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized,);
+	}
+
+	fn test_be<T>(value: T)
+	where
+		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
+	{
+		let serialized = Serializer::to_bytes(&value, true).unwrap();
+		let deserialized: T = Deserializer::from_bytes(&serialized, true).unwrap();
+		assert_eq!(value, deserialized,);
+	}
+
+	fn test_sd<T>(value: T)
+	where
+		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
+	{
+		for big_endian in [false, true] {
+			let config = Config {
+				self_describing: true,
+				..Config::new(big_endian)
+			};
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let deserialized: T =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+		}
+	}
+
+	fn test_undersized<T>(value: T)
+	where
+		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
+	{
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let shrunk = serialized[0..serialized.len() - 1].to_vec();
+
+		assert!(Deserializer::from_bytes::<T>(&shrunk, false).is_err());
+	}
+
+	#[test]
+	fn test_thread() {
+		let handle = thread::spawn(|| Serializer::to_bytes(&(String::from("ABC"), 0x41_u8), false));
+		let serialized = handle.join().unwrap().unwrap();
+		let deserialized: (String, u8) = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, (String::from("ABC"), 0x41));
+	}
+
+	// Checks a value is written as the bytes in `tests/golden`, and that those bytes are read back
+	// as the value. Set `UPDATE_GOLDEN` to rewrite the files after a deliberate format change.
+	fn test_golden<T>(name: &str, value: &T, config: &Config)
+	where
+		T: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
+	{
+		let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+			.join("tests/golden")
+			.join(name);
+		let serialized = Serializer::to_bytes_with_config(value, config).unwrap();
+		if std::env::var_os("UPDATE_GOLDEN").is_some() {
+			std::fs::write(&path, &serialized).unwrap();
+		}
+		let golden = std::fs::read(&path).unwrap();
+		assert_eq!(serialized, golden, "the format of {} has changed", name);
+		let deserialized: T = Deserializer::from_bytes_with_config(&golden, config).unwrap();
+		assert_eq!(value, &deserialized);
+	}
+
+	#[test]
+	fn test_golden_files() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("test"),
+		};
+		test_golden("struct.bin", &value, &Config::default());
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		test_golden("struct_self_describing.bin", &value, &config);
+
+		let value = vec![
+			TestEnum::NewTypeVariant(0x41),
+			TestEnum::StructVariant { a: 0x42, b: 0x43 },
+			TestEnum::TupleVariant(0x44, 0x45, 0x46),
+			TestEnum::UnitVariant,
+		];
+		test_golden("enum.bin", &value, &Config::default());
+
+		let mut value: BTreeMap<String, Option<u16>> = BTreeMap::new();
+		value.insert(String::from("a"), Some(0x1234));
+		value.insert(String::from("b"), None);
+		test_golden("map.bin", &value, &Config::default());
+
+		let value: Vec<u32> = vec![0x01, 0x0203, 0x04050607];
+		test_golden("vec_le.bin", &value, &Config::new(false));
+		test_golden("vec_be.bin", &value, &Config::new(true));
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct FixedLengths {
+		pub values: [u32; 4],
+		pub pair: (u8, i16),
+		pub empty: [u8; 0],
+		pub list: Vec<u8>,
+	}
+
+	#[test]
+	fn test_omit_tuple_lengths() {
+		let config = Config {
+			omit_tuple_lengths: true,
+			..Config::default()
+		};
+		let value = [0x01_u32, 0x02, 0x03, 0x04];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(
+			serialized,
+			vec![
+				0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00,
+				0x00, 0x00
+			]
+		);
+		let deserialized: [u32; 4] =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		let value = FixedLengths {
+			values: [0x41, 0x42, 0x43, 0x44],
+			pair: (0x45, -1),
+			empty: [],
+			list: vec![0x46],
+		};
+		for big_endian in [false, true] {
+			let config = Config {
+				omit_tuple_lengths: true,
+				..Config::new(big_endian)
+			};
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			// Only the struct and the Vec have lengths
+			assert_eq!(serialized.len(), 1 + 16 + 3 + 2);
+			let deserialized: FixedLengths =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+			let with_lengths = Serializer::to_bytes(&value, big_endian).unwrap();
+			assert_eq!(serialized.len() + 3, with_lengths.len());
+		}
+
+		// Self-describing mode keeps the lengths
+		let config = Config {
+			omit_tuple_lengths: true,
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: FixedLengths =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+
+		// Tuple structs keep their lengths too
+		let config = Config {
+			omit_tuple_lengths: true,
+			..Config::default()
+		};
+		let value = TupleStruct(0x01, 0x02, 0x03);
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(serialized, vec![0x03, 0x01, 0x02, 0x03]);
+		let deserialized: TupleStruct =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct NativeSizes {
+		pub len: usize,
+		pub offset: isize,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct FixedSizes {
+		pub len: u64,
+		pub offset: i64,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct NarrowSizes {
+		pub len: u32,
+		pub offset: i32,
+	}
+
+	#[test]
+	fn test_validate_element_size() {
+		let config = Config {
+			validate_element_size: true,
+			..Config::default()
+		};
+		let value: Vec<u32> = vec![0x01, 0x02];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		// The length, then the size of each element
+		assert_eq!(
+			serialized,
+			vec![0x02, 0x04, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]
+		);
+		let deserialized: Vec<u32> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		assert_eq!(
+			Deserializer::from_bytes_with_config::<Vec<u64>>(&serialized, &config),
+			Err(BinaryError::Message {
+				message: String::from("sequence elements were written 4 bytes wide, but read as 8")
+			})
+		);
+		assert!(Deserializer::from_bytes_with_config::<Vec<u16>>(&serialized, &config).is_err());
+		// Without the option the same mistake can go unnoticed
+		let serialized = Serializer::to_bytes(&vec![0x01_u32, 0x02, 0x03, 0x04], false).unwrap();
+		let deserialized: Vec<u16> = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, vec![0x01, 0x00, 0x02, 0x00]);
+
+		// Sequences of other values are not checked, nor are tuples and arrays
+		let value = FixedLengths {
+			values: [0x41, 0x42, 0x43, 0x44],
+			pair: (0x45, -1),
+			empty: [],
+			list: vec![0x46],
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(
+			serialized.len(),
+			Serializer::to_bytes(&value, false).unwrap().len() + 1
+		);
+		let deserialized: FixedLengths =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		let value = vec![vec![String::from("A")], Vec::new()];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(serialized[..4], [0x02, 0x00, 0x01, 0x00]);
+		let deserialized: Vec<Vec<String>> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		let value: Vec<u8> = Vec::new();
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		assert_eq!(serialized, vec![0x00, 0x00]);
+
+		// Nor are the fields of tuple and struct variants
+		let value = vec![
+			TestEnum::TupleVariant(0x41, 0x42, 0x43),
+			TestEnum::StructVariant { a: 0x44, b: 0x45 },
+			TestEnum::NewTypeVariant(0x46),
+			TestEnum::UnitVariant,
+		];
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Vec<TestEnum> =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	// A readable value, rather than an approximation of pi
+	#[allow(clippy::approx_constant)]
+	fn test_debug_floats() {
+		let config = Config {
+			self_describing: true,
+			debug_floats: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&3.14159_f64, &config).unwrap();
+		// The type tag, the length and the digits
+		assert_eq!(serialized[..2], [0x0E, 0x07]);
+		assert_eq!(&serialized[2..], b"3.14159");
+		let deserialized: f64 = Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(deserialized, 3.14159);
+
+		let value = (
+			1.5_f32,
+			-0.1_f64,
+			f32::INFINITY,
+			f64::MIN_POSITIVE,
+			0x41_u32,
+		);
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: (f32, f64, f32, f64, u32) =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		let serialized = Serializer::to_bytes_with_config(&f64::NAN, &config).unwrap();
+		let deserialized: f64 = Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert!(deserialized.is_nan());
+
+		// Floats are written as bytes outside self-describing mode
+		let config = Config {
+			debug_floats: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&3.14159_f64, &config).unwrap();
+		assert_eq!(serialized, 3.14159_f64.to_le_bytes());
+	}
+
+	#[test]
+	fn test_platform_sizes() {
+		// Serde writes `usize` as a `u64` and `isize` as an `i64`, so they take 8 bytes whatever
+		// the platform, and a 32 bit producer and a 64 bit consumer agree
+		let value = NativeSizes {
+			len: 0x41,
+			offset: -2,
+		};
+		for big_endian in [false, true] {
+			let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+			assert_eq!(serialized.len(), 1 + 8 + 8);
+			let fixed = FixedSizes {
+				len: 0x41,
+				offset: -2,
+			};
+			assert_eq!(
+				serialized,
+				Serializer::to_bytes(&fixed, big_endian).unwrap()
+			);
+			let deserialized: NativeSizes =
+				Deserializer::from_bytes(&serialized, big_endian).unwrap();
+			assert_eq!(value, deserialized);
+			let deserialized: FixedSizes =
+				Deserializer::from_bytes(&serialized, big_endian).unwrap();
+			assert_eq!(fixed, deserialized);
+		}
+
+		// Narrower numbers are rejected rather than read as part of a `usize`
+		let narrow = NarrowSizes {
+			len: 0x41,
+			offset: -2,
+		};
+		let serialized = Serializer::to_bytes(&narrow, false).unwrap();
+		assert!(Deserializer::from_bytes::<NativeSizes>(&serialized, false).is_err());
+	}
+
+	// Reports allocations made inside `assert_no_alloc` on the same thread, so tests running in
+	// parallel do not interfere
+	#[global_allocator]
+	static ALLOCATOR: AllocDisabler = AllocDisabler;
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Sample {
+		pub id: u32,
+		pub channel: u8,
+		pub timestamp: i64,
+		pub reading: i16,
+		pub flags: [u8; 4],
+	}
+
+	#[test]
+	fn test_no_allocation() {
+		let value = Sample {
+			id: 0x41,
+			channel: 0x02,
+			timestamp: -1,
+			reading: 0x1234,
+			flags: [0x01, 0x02, 0x03, 0x04],
+		};
+		for big_endian in [false, true] {
+			let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+			reset_violation_count();
+			let deserialized =
+				assert_no_alloc(|| Deserializer::from_bytes::<Sample>(&serialized, big_endian));
+			assert_eq!(violation_count(), 0);
+			assert_eq!(deserialized.unwrap(), value);
+		}
+	}
+
+	// Writes a sequence of sequences without size hints, so each is written as of unknown length
+	struct UnsizedNested<'a>(&'a [Vec<u32>]);
+
+	struct UnsizedInner<'a>(&'a [u32]);
+
+	impl Serialize for UnsizedNested<'_> {
+		fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+		where
+			S: serde::Serializer,
+		{
+			serializer.collect_seq(self.0.iter().filter(|_| true).map(|v| UnsizedInner(v)))
+		}
+	}
+
+	impl Serialize for UnsizedInner<'_> {
+		fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+		where
+			S: serde::Serializer,
+		{
+			serializer.collect_seq(self.0.iter().filter(|_| true))
+		}
+	}
+
+	#[test]
+	fn test_unknown_length_allocation() {
+		let value: Vec<Vec<u32>> = (0..1000).map(|i| vec![i; (i % 4) as usize]).collect();
+		let expected = Serializer::to_bytes(&value, false).unwrap();
+		reset_violation_count();
+		let serialized = assert_no_alloc(|| Serializer::to_bytes(&UnsizedNested(&value), false));
+		// Only the output grows, rather than each nested collection needing a buffer
+		assert!(violation_count() < 100);
+		let serialized = serialized.unwrap();
+		assert_eq!(serialized, expected);
+		let deserialized: Vec<Vec<u32>> = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(deserialized, value);
 	}
 }
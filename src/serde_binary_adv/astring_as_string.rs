@@ -0,0 +1,136 @@
+//! Serialize fixed length ASCII strings in the same format as `String`.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::astring_as_string")]` on a `FixedLengthString`
+//! field. A `FixedLengthString` is normally written as its raw bytes; this writes it as a length
+//! followed by UTF-8, so that a field can be changed between `String` and `FixedLengthString`
+//! without changing the format.
+//!
+//! Only the characters up to the first `0x00` are written, leaving out the padding, so
+//! `FixedLengthString::<8>` holding `"ABC"` writes the same bytes as `String::from("ABC")`.
+//! Reading pads the string with `0x00` again. Reading a string that is not ASCII, or that is longer
+//! than the `FixedLengthString`, is an error.
+
+use lowlevel_types::ascii::FixedLengthString;
+use serde::{Deserialize, Deserializer, Serializer, de};
+
+/// Serializes a fixed length string as a length followed by its characters
+pub fn serialize<S, const N: usize>(
+	value: &FixedLengthString<N>,
+	serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+{
+	let s: String = value.trimmed().iter().map(|c| c.char()).collect();
+	serializer.serialize_str(&s)
+}
+
+/// Deserializes a string into a fixed length string, padding it with `0x00`
+pub fn deserialize<'de, D, const N: usize>(
+	deserializer: D,
+) -> Result<FixedLengthString<N>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let s = String::deserialize(deserializer)?;
+	FixedLengthString::try_from(s.as_str()).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+	use lowlevel_types::ascii::FixedLengthString;
+	use serde::{Deserialize, Serialize};
+
+	#[cfg(feature = "streaming")]
+	use crate::stream;
+	use crate::{Config, Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Named {
+		pub id: u8,
+		#[serde(with = "crate::astring_as_string")]
+		pub name: FixedLengthString<8>,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct PlainNamed {
+		pub id: u8,
+		pub name: String,
+	}
+
+	#[test]
+	fn test_same_as_string() {
+		let value = Named {
+			id: 0x41,
+			name: FixedLengthString::try_from("ABC").unwrap(),
+		};
+		let plain = PlainNamed {
+			id: 0x41,
+			name: String::from("ABC"),
+		};
+		let configs = [
+			Config::default(),
+			Config::new(true),
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+		];
+		for config in configs {
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			assert_eq!(
+				serialized,
+				Serializer::to_bytes_with_config(&plain, &config).unwrap()
+			);
+			let deserialized: Named =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+			let deserialized: PlainNamed =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(plain, deserialized);
+		}
+	}
+
+	#[cfg(feature = "streaming")]
+	#[test]
+	fn test_same_as_string_stream() {
+		let value = Named {
+			id: 0x41,
+			name: FixedLengthString::try_from("ABC").unwrap(),
+		};
+		let plain = PlainNamed {
+			id: 0x41,
+			name: String::from("ABC"),
+		};
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		stream::Serializer::write_bytes(buf, &value, false).unwrap();
+		assert_eq!(*buf, Serializer::to_bytes(&plain, false).unwrap());
+		let deserialized: Named =
+			stream::Deserializer::read_bytes(&mut buf.as_slice(), false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_not_ascii() {
+		let serialized = Serializer::to_bytes(
+			&PlainNamed {
+				id: 0x41,
+				name: String::from("👿"),
+			},
+			false,
+		)
+		.unwrap();
+		assert!(Deserializer::from_bytes::<Named>(&serialized, false).is_err());
+
+		// Longer than the fixed length
+		let serialized = Serializer::to_bytes(
+			&PlainNamed {
+				id: 0x41,
+				name: String::from("ABCDEFGHI"),
+			},
+			false,
+		)
+		.unwrap();
+		assert!(Deserializer::from_bytes::<Named>(&serialized, false).is_err());
+	}
+}
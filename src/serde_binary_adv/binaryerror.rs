@@ -1,4 +1,5 @@
 use std::fmt::{self, Display};
+use std::io::{self, ErrorKind};
 use std::{self, string::FromUtf8Error};
 
 use serde::{de, ser};
@@ -23,6 +24,11 @@ pub enum BinaryError {
 		/// The expected value
 		expected: u8,
 	},
+	/// an option flag that is neither `NONE` nor `SOME`
+	InvalidOptionFlag {
+		/// The actual value
+		actual: u8,
+	},
 	/// inavlid length
 	InvalidLength {
 		/// The actual value
@@ -39,6 +45,29 @@ pub enum BinaryError {
 	},
 	/// unexpected type
 	UnexpectedType,
+	/// the output buffer is too small to hold the serialized value
+	BufferTooSmall,
+	/// the data was written for a different schema
+	SchemaMismatch {
+		/// The fingerprint stored with the data
+		actual: u64,
+		/// The fingerprint of the type being deserialized
+		expected: u64,
+	},
+	/// a length, or the total of the lengths, is larger than the configured limit
+	LengthLimitExceeded {
+		/// The actual value
+		actual: usize,
+		/// The limit
+		limit: usize,
+	},
+	/// values are nested deeper than the configured limit
+	DepthLimitExceeded {
+		/// The limit
+		limit: usize,
+	},
+	/// a non-blocking stream had no data ready, so the value can be read again once it has
+	WouldBlock,
 }
 
 impl ser::Error for BinaryError {
@@ -68,6 +97,11 @@ impl Display for BinaryError {
 				"missing or invalid type flag, actual 0x{:X}, expected 0x{:X}",
 				actual, expected
 			),
+			BinaryError::InvalidOptionFlag { actual } => write!(
+				f,
+				"invalid option flag 0x{:X}, expected 0x00 for None or 0xFF for Some",
+				actual
+			),
 			BinaryError::InvalidLength { actual, expected } => {
 				write!(
 					f,
@@ -79,6 +113,23 @@ impl Display for BinaryError {
 				write!(f, "invalid name, actual {}, expected {}", actual, expected)
 			}
 			BinaryError::UnexpectedType => write!(f, "unexpected type"),
+			BinaryError::BufferTooSmall => write!(f, "buffer too small"),
+			BinaryError::SchemaMismatch { actual, expected } => write!(
+				f,
+				"schema mismatch, actual fingerprint 0x{:016X}, expected 0x{:016X}",
+				actual, expected
+			),
+			BinaryError::LengthLimitExceeded { actual, limit } => {
+				write!(
+					f,
+					"length limit exceeded, actual {}, limit {}",
+					actual, limit
+				)
+			}
+			BinaryError::DepthLimitExceeded { limit } => {
+				write!(f, "values are nested deeper than the limit of {}", limit)
+			}
+			BinaryError::WouldBlock => write!(f, "operation would block"),
 		}
 	}
 }
@@ -93,8 +144,30 @@ impl From<FromUtf8Error> for BinaryError {
 	}
 }
 
+impl From<io::Error> for BinaryError {
+	fn from(e: io::Error) -> Self {
+		// Errors raised by readers and writers from this crate, such as a LimitedWriter, are
+		// passed through as is
+		if let Some(inner) = e
+			.get_ref()
+			.and_then(|inner| inner.downcast_ref::<BinaryError>())
+		{
+			return inner.clone();
+		}
+		match e.kind() {
+			ErrorKind::UnexpectedEof => BinaryError::UnexpectedEndOfInput,
+			ErrorKind::WouldBlock => BinaryError::WouldBlock,
+			_ => BinaryError::Message {
+				message: e.to_string(),
+			},
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
+	use std::io::{self, ErrorKind};
+
 	use crate::BinaryError;
 
 	#[test]
@@ -108,6 +181,22 @@ mod tests {
 		test_display_specific(BinaryError::InvalidBytes, "invalid byte sequence");
 		test_display_specific(BinaryError::UnexpectedEndOfInput, "unexpected end of input");
 		test_display_specific(BinaryError::UnexpectedType, "unexpected type");
+		test_display_specific(BinaryError::BufferTooSmall, "buffer too small");
+		test_display_specific(BinaryError::WouldBlock, "operation would block");
+		test_display_specific(
+			BinaryError::SchemaMismatch {
+				actual: 0x01,
+				expected: 0xABCD,
+			},
+			"schema mismatch, actual fingerprint 0x0000000000000001, expected 0x000000000000ABCD",
+		);
+		test_display_specific(
+			BinaryError::LengthLimitExceeded {
+				actual: 2,
+				limit: 1,
+			},
+			"length limit exceeded, actual 2, limit 1",
+		);
 		test_display_specific(BinaryError::UnexpectedEndOfInput, "unexpected end of input");
 		test_display_specific(
 			BinaryError::InvalidLength {
@@ -125,11 +214,15 @@ mod tests {
 		);
 		test_display_specific(
 			BinaryError::MissingOrInvalidFlag {
-				actual: 0xff,
+				actual: 0xFF,
 				expected: 0x80,
 			},
 			"missing or invalid type flag, actual 0xFF, expected 0x80",
 		);
+		test_display_specific(
+			BinaryError::InvalidOptionFlag { actual: 0x7F },
+			"invalid option flag 0x7F, expected 0x00 for None or 0xFF for Some",
+		);
 	}
 
 	fn test_display_specific(error: BinaryError, expected: &str) {
@@ -143,4 +236,16 @@ mod tests {
 		let f = <BinaryError as serde::de::Error>::custom("test");
 		test_display_specific(f, "test");
 	}
+
+	#[test]
+	fn test_from_io_error() {
+		let e = BinaryError::from(io::Error::new(ErrorKind::BrokenPipe, "pipe closed"));
+		test_display_specific(e, "pipe closed");
+		let e = BinaryError::from(io::Error::from(ErrorKind::UnexpectedEof));
+		assert_eq!(e, BinaryError::UnexpectedEndOfInput);
+		let e = BinaryError::from(io::Error::from(ErrorKind::WouldBlock));
+		assert_eq!(e, BinaryError::WouldBlock);
+		let e = BinaryError::from(io::Error::other(BinaryError::BufferTooSmall));
+		assert_eq!(e, BinaryError::BufferTooSmall);
+	}
 }
@@ -0,0 +1,213 @@
+//! Serialize byte buffers with long runs of the same byte using run-length encoding.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::byte_rle")]` on a `Vec<u8>` field, such as an
+//! image or a mask that is mostly zero. The buffer is written as a single byte blob in the
+//! PackBits scheme: each control byte `n` is followed either by `n + 1` literal bytes, for `n` from
+//! `0` to `127`, or by one byte repeated `257 - n` times, for `n` from `129` to `255`. A control
+//! byte of `128` is skipped.
+//!
+//! A buffer of 4096 zero bytes is written in 65 bytes. A buffer without runs grows by one byte in
+//! every 128.
+
+use std::fmt;
+
+use serde::{
+	Deserializer, Serializer,
+	de::{self, Visitor},
+};
+
+// The most bytes a single literal or repeat run can hold
+const MAX_RUN: usize = 128;
+
+// The shortest run worth writing as a repeat, rather than as part of a literal run
+const MIN_REPEAT: usize = 3;
+
+/// Serializes a byte buffer as PackBits runs
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: AsRef<[u8]>,
+{
+	serializer.serialize_bytes(&encode(value.as_ref()))
+}
+
+/// Deserializes a byte buffer from PackBits runs
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: From<Vec<u8>>,
+{
+	Ok(T::from(deserializer.deserialize_byte_buf(ByteRleVisitor)?))
+}
+
+// Returns the number of times the byte at the start of `data` is repeated, up to `MAX_RUN`
+fn run_len(data: &[u8]) -> usize {
+	data.iter()
+		.take(MAX_RUN)
+		.take_while(|b| **b == data[0])
+		.count()
+}
+
+fn encode(data: &[u8]) -> Vec<u8> {
+	let mut res: Vec<u8> = Vec::with_capacity(data.len() + data.len().div_ceil(MAX_RUN));
+	let mut i: usize = 0;
+	while i < data.len() {
+		let run = run_len(&data[i..]);
+		if run >= MIN_REPEAT {
+			res.push((257 - run) as u8);
+			res.push(data[i]);
+			i += run;
+			continue;
+		}
+		// A literal run ends where a run long enough to repeat begins
+		let start = i;
+		while i < data.len() && i - start < MAX_RUN && run_len(&data[i..]) < MIN_REPEAT {
+			i += 1;
+		}
+		res.push((i - start - 1) as u8);
+		res.extend_from_slice(&data[start..i]);
+	}
+	res
+}
+
+struct ByteRleVisitor;
+
+impl<'de> Visitor<'de> for ByteRleVisitor {
+	type Value = Vec<u8>;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a run-length encoded byte buffer")
+	}
+
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		let mut res: Vec<u8> = Vec::new();
+		let mut offset: usize = 0;
+		while offset < v.len() {
+			let control = v[offset];
+			offset += 1;
+			match control {
+				0..=127 => {
+					let len = usize::from(control) + 1;
+					let literal = v
+						.get(offset..offset + len)
+						.ok_or_else(|| de::Error::invalid_length(v.len(), &self))?;
+					res.extend_from_slice(literal);
+					offset += len;
+				}
+				128 => {}
+				_ => {
+					let byte = *v
+						.get(offset)
+						.ok_or_else(|| de::Error::invalid_length(v.len(), &self))?;
+					res.resize(res.len() + 257 - usize::from(control), byte);
+					offset += 1;
+				}
+			}
+		}
+		Ok(res)
+	}
+
+	fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.visit_bytes(&v)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Config, Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Mask {
+		pub width: u16,
+		#[serde(with = "crate::byte_rle")]
+		pub data: Vec<u8>,
+	}
+
+	fn round_trip(data: Vec<u8>) -> usize {
+		let value = Mask { width: 0x40, data };
+		let mut len = 0;
+		for big_endian in [false, true] {
+			let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+			let deserialized: Mask = Deserializer::from_bytes(&serialized, big_endian).unwrap();
+			assert_eq!(value, deserialized);
+			len = serialized.len();
+		}
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Mask =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+		len
+	}
+
+	#[test]
+	fn test_zeros() {
+		// Struct length, width, blob length and 32 repeat runs of two bytes each
+		assert_eq!(round_trip(vec![0x00; 4096]), 1 + 2 + 1 + 64);
+		let serialized = Serializer::to_bytes(
+			&Mask {
+				width: 0x40,
+				data: vec![0x00; 130],
+			},
+			false,
+		)
+		.unwrap();
+		assert_eq!(serialized[3..], [0x05, 0x81, 0x00, 0x01, 0x00, 0x00]);
+		assert_eq!(round_trip(Vec::new()), 1 + 2 + 1);
+	}
+
+	#[test]
+	fn test_random() {
+		// A xorshift generator, so the buffer is the same on every run
+		let mut state: u32 = 0x2545F491;
+		let data: Vec<u8> = (0..4096)
+			.map(|_| {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				state as u8
+			})
+			.collect();
+		// About one control byte for every 128 bytes, plus one for each chance run that ends a
+		// literal run early
+		assert!(round_trip(data) <= 4096 + 4096 / 64);
+	}
+
+	#[test]
+	fn test_mixed() {
+		let mut data: Vec<u8> = vec![0x41, 0x42, 0x42, 0x43];
+		data.extend_from_slice(&[0xFF; 200]);
+		data.extend(0..=255_u8);
+		data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+		assert!(round_trip(data.clone()) < data.len());
+
+		let serialized = Serializer::to_bytes(
+			&Mask {
+				width: 0x40,
+				data: vec![0x41, 0x42, 0x42, 0x43, 0x43, 0x43],
+			},
+			false,
+		)
+		.unwrap();
+		// Three literal bytes, then a repeat of three
+		assert_eq!(serialized[4..], [0x02, 0x41, 0x42, 0x42, 0xFE, 0x43]);
+	}
+
+	#[test]
+	fn test_truncated() {
+		// Struct length, width and a blob claiming two literal bytes, with only one following
+		let serialized = vec![0x02, 0x40, 0x00, 0x02, 0x01, 0x41];
+		assert!(Deserializer::from_bytes::<Mask>(&serialized, false).is_err());
+	}
+}
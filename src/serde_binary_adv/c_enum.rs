@@ -0,0 +1,136 @@
+//! Serialize C-style enums as a single discriminant byte.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::c_enum")]` on a field whose type is an enum with
+//! only unit variants. Enums are normally written as a flag followed by a `u32` variant index,
+//! which is five bytes; this writes the variant index as one `u8` instead, so the enum can have at
+//! most 256 variants.
+//!
+//! Serializing a variant that holds data, or one with an index above 255, is an error.
+
+use serde::{
+	Deserialize, Deserializer, Serialize, Serializer,
+	de::{self, IntoDeserializer},
+	ser,
+};
+
+use crate::serde_binary_adv::common::flags::UNIT_VARIANT;
+
+/// Serializes a unit variant as its `u8` index
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: Serialize,
+{
+	// Let the enum report its variant index by writing it in the standard form
+	let bytes = crate::Serializer::to_bytes(value, false).map_err(ser::Error::custom)?;
+	let index = match bytes.as_slice() {
+		[UNIT_VARIANT, index @ ..] if index.len() == 4 => {
+			u32::from_le_bytes([index[0], index[1], index[2], index[3]])
+		}
+		_ => {
+			return Err(ser::Error::custom(
+				"c_enum requires an enum with only unit variants",
+			));
+		}
+	};
+	match u8::try_from(index) {
+		Ok(v) => serializer.serialize_u8(v),
+		Err(_) => Err(ser::Error::custom(format!(
+			"c_enum supports at most 256 variants, found variant index {}",
+			index
+		))),
+	}
+}
+
+/// Deserializes a unit variant from its `u8` index
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: Deserialize<'de>,
+{
+	let index = u8::deserialize(deserializer)?;
+	let variant: de::value::U32Deserializer<de::value::Error> =
+		u32::from(index).into_deserializer();
+	T::deserialize(variant).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+	enum Direction {
+		North,
+		East,
+		South,
+		West,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Shape {
+		Point,
+		Circle(u8),
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Heading {
+		#[serde(with = "crate::c_enum")]
+		pub direction: Direction,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Outline {
+		#[serde(with = "crate::c_enum")]
+		pub shape: Shape,
+	}
+
+	#[test]
+	fn test_round_trip() {
+		for direction in [
+			Direction::North,
+			Direction::East,
+			Direction::South,
+			Direction::West,
+		] {
+			let value = Heading { direction };
+			let serialized = Serializer::to_bytes(&value, true).unwrap();
+			// Struct length and the discriminant
+			assert_eq!(serialized, vec![0x01, direction as u8]);
+			let deserialized: Heading = Deserializer::from_bytes(&serialized, true).unwrap();
+			assert_eq!(value, deserialized);
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct PlainHeading {
+		pub direction: Direction,
+	}
+
+	#[test]
+	fn test_mismatch() {
+		// Data written with one enum encoding and read with the other is rejected, since a
+		// discriminant byte is never a variant flag and a variant flag is never a valid index
+		for direction in [Direction::North, Direction::West] {
+			let serialized = Serializer::to_bytes(&Heading { direction }, false).unwrap();
+			assert!(Deserializer::from_bytes::<PlainHeading>(&serialized, false).is_err());
+			let serialized = Serializer::to_bytes(&PlainHeading { direction }, false).unwrap();
+			assert!(Deserializer::from_bytes::<Heading>(&serialized, false).is_err());
+		}
+	}
+
+	#[test]
+	fn test_invalid() {
+		assert!(Deserializer::from_bytes::<Heading>(&[0x01, 0x04], false).is_err());
+		let value = Outline {
+			shape: Shape::Circle(0x41),
+		};
+		assert!(Serializer::to_bytes(&value, false).is_err());
+		let value = Outline {
+			shape: Shape::Point,
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized, vec![0x01, 0x00]);
+	}
+}
@@ -0,0 +1,174 @@
+//! Serialize a list of structs column by column rather than row by row.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::columnar")]` on a `Vec` of a struct that implements
+//! `Columnar`. The values of each field are gathered into a column, and the columns are written
+//! one after another, each with its length, so that all the values of the first field come
+//! before all those of the second. Similar values end up next to each other, which compresses far
+//! better for analytical data.
+//!
+//! `Columnar` converts between the rows and their columns, and the `impl_columnar!` macro
+//! implements it for a struct with named fields from a list of the fields and their types:
+//!
+//! ```
+//! #[derive(Clone)]
+//! struct Reading {
+//!     id: u32,
+//!     value: f64,
+//! }
+//!
+//! serde_binary_adv::impl_columnar!(Reading { id: u32, value: f64 });
+//! ```
+//!
+//! The columns are a tuple with a `Vec` for each field, and serde only implements tuples of up to
+//! 16 elements, so `impl_columnar!` does not compile for structs with more than 16 fields.
+//!
+//! The fields are cloned into their columns when writing. Reading columns of different lengths is
+//! an error. A struct without fields has no columns, so a list of them always reads as empty.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned};
+
+/// A struct that can be written as one column per field
+pub trait Columnar: Sized {
+	/// The columns, usually a tuple with a `Vec` for each field
+	type Columns: Serialize + DeserializeOwned;
+
+	/// Gathers the values of each field of the rows into a column
+	fn to_columns(rows: &[Self]) -> Self::Columns;
+
+	/// Assembles rows from the columns, or returns `None` if the columns differ in length
+	fn from_columns(columns: Self::Columns) -> Option<Vec<Self>>;
+}
+
+/// Implements `Columnar` for a struct with named fields, whose field types implement `Clone`.
+///
+/// Every field must be listed with its type, as `impl_columnar!(Reading { id: u32, value: f64 })`.
+#[macro_export]
+macro_rules! impl_columnar {
+	($ty:ty { $($field:ident: $field_ty:ty),* $(,)? }) => {
+		impl $crate::columnar::Columnar for $ty {
+			type Columns = ($(Vec<$field_ty>,)*);
+
+			#[allow(unused_variables, clippy::unused_unit)]
+			fn to_columns(rows: &[Self]) -> Self::Columns {
+				($(rows.iter().map(|row| row.$field.clone()).collect::<Vec<$field_ty>>(),)*)
+			}
+
+			#[allow(unused_mut, unused_variables)]
+			fn from_columns(columns: Self::Columns) -> Option<Vec<Self>> {
+				let ($($field,)*) = columns;
+				let lens = [$($field.len()),*];
+				let len = lens.first().copied().unwrap_or(0);
+				if lens.iter().any(|l| *l != len) {
+					return None;
+				}
+				$(let mut $field = $field.into_iter();)*
+				(0..len)
+					.map(|_| Some(Self { $($field: $field.next()?,)* }))
+					.collect()
+			}
+		}
+	};
+}
+
+/// Serializes rows as one column per field
+pub fn serialize<S, T>(value: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: Columnar,
+{
+	T::to_columns(value).serialize(serializer)
+}
+
+/// Deserializes rows from one column per field
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: Columnar,
+{
+	let columns = T::Columns::deserialize(deserializer)?;
+	T::from_columns(columns).ok_or_else(|| serde::de::Error::custom("columns differ in length"))
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Config, Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+	struct Test {
+		pub byte: u8,
+		pub string: String,
+	}
+
+	impl_columnar!(Test {
+		byte: u8,
+		string: String
+	});
+
+	#[derive(Debug, PartialEq, Clone)]
+	struct Empty {}
+
+	impl_columnar!(Empty {});
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Table {
+		#[serde(with = "crate::columnar")]
+		pub rows: Vec<Test>,
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let value = Table {
+			rows: (0..100)
+				.map(|i| Test {
+					byte: i as u8,
+					string: format!("row {}", i),
+				})
+				.collect(),
+		};
+		let configs = [
+			Config::default(),
+			Config::new(true),
+			Config {
+				self_describing: true,
+				..Config::default()
+			},
+		];
+		for config in configs {
+			let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+			let deserialized: Table =
+				Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+			assert_eq!(value, deserialized);
+		}
+
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Struct length, the number of columns, then the length of the first column and its bytes
+		assert_eq!(serialized[..5], [0x01, 0x02, 0x64, 0x00, 0x01]);
+		assert_eq!(serialized[3..103], (0..100).collect::<Vec<u8>>());
+		// Then the length of the second column, and its first string
+		assert_eq!(serialized[103..109], [0x64, 0x05, b'r', b'o', b'w', b' ']);
+
+		let empty = Table { rows: Vec::new() };
+		let serialized = Serializer::to_bytes(&empty, false).unwrap();
+		assert_eq!(serialized, vec![0x01, 0x02, 0x00, 0x00]);
+		let deserialized: Table = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(empty, deserialized);
+	}
+
+	#[test]
+	fn test_no_fields() {
+		use super::Columnar;
+
+		Empty::to_columns(&[Empty {}]);
+		// There are no columns to write, so no rows to read
+		assert_eq!(Empty::from_columns(()), Some(Vec::new()));
+	}
+
+	#[test]
+	fn test_uneven_columns() {
+		// Two bytes but only one string
+		let serialized = vec![0x01, 0x02, 0x02, 0x41, 0x42, 0x01, 0x01, b'A'];
+		assert!(Deserializer::from_bytes::<Table>(&serialized, false).is_err());
+	}
+}
@@ -2,9 +2,10 @@ use super::BinaryError;
 use std::mem::size_of;
 
 /// How to serialize multibyte sequences (e.g. u128, i128, f64)
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ByteFormat {
 	/// Little Endian format
+	#[default]
 	LittleEndian,
 	/// Big Endian format
 	BigEndian,
@@ -13,12 +14,51 @@ pub enum ByteFormat {
 	NativeEndian,
 }
 
+impl ByteFormat {
+	/// Returns true if the format writes the most significant byte first on this platform
+	pub fn is_big_endian(&self) -> bool {
+		match self {
+			ByteFormat::LittleEndian => false,
+			ByteFormat::BigEndian => true,
+			ByteFormat::NativeEndian => cfg!(target_endian = "big"),
+		}
+	}
+}
+
 pub mod flags {
 	pub const NONE: u8 = 0x00;
 	pub const SOME: u8 = 0xFF;
 	pub const UNIT_VARIANT: u8 = 0xFE;
 	pub const NONUNIT_VARIANT: u8 = 0xFD;
 	pub const STRUCT_VARIANT: u8 = 0xFC;
+	// Written after a value in a stream to show it is complete
+	#[cfg(feature = "streaming")]
+	pub const END: u8 = 0xFB;
+}
+
+/// The type tags written before each value in self-describing mode. `Option` values and enum
+/// variants use the flags instead, which do not overlap with these.
+pub mod tags {
+	pub const UNIT: u8 = 0x01;
+	pub const BOOL: u8 = 0x02;
+	pub const U8: u8 = 0x03;
+	pub const U16: u8 = 0x04;
+	pub const U32: u8 = 0x05;
+	pub const U64: u8 = 0x06;
+	pub const U128: u8 = 0x07;
+	pub const I8: u8 = 0x08;
+	pub const I16: u8 = 0x09;
+	pub const I32: u8 = 0x0A;
+	pub const I64: u8 = 0x0B;
+	pub const I128: u8 = 0x0C;
+	pub const F32: u8 = 0x0D;
+	pub const F64: u8 = 0x0E;
+	pub const CHAR: u8 = 0x0F;
+	pub const STR: u8 = 0x10;
+	pub const BYTES: u8 = 0x11;
+	pub const SEQ: u8 = 0x12;
+	pub const MAP: u8 = 0x13;
+	pub const NEWTYPE: u8 = 0x14;
 }
 
 /// an Ok(()) or Err(serde_binary_adv::BinaryError)
@@ -27,27 +67,33 @@ pub type Result<T> = std::result::Result<T, super::BinaryError>;
 /// Encodes an `usize` using a hybrid continuation bit and 3-bit length prefix scheme.
 pub fn compress_usize(value: usize) -> Vec<u8> {
 	let mut res: Vec<u8> = Vec::new();
+	compress_usize_into(value, &mut res);
+	res
+}
 
+/// Appends an `usize` to `output` in the same form as `compress_usize`, without allocating a
+/// buffer for it.
+pub fn compress_usize_into(value: usize, output: &mut Vec<u8>) {
 	if value <= 0b01111111 {
-		res.push(value as u8);
-		return res;
+		output.push(value as u8);
+		return;
 	}
 
-	let mut v: usize = value.clone();
+	let start = output.len();
+	let mut v: usize = value;
 	let mut byte_counter: u8 = 0;
 
-	res.push((0b10000000 | (v & 0b01111111)) as u8);
-	v = v >> 7;
-	res.push((0b00011111 & v) as u8);
-	v = v >> 5;
+	output.push((0b10000000 | (v & 0b01111111)) as u8);
+	v >>= 7;
+	output.push((0b00011111 & v) as u8);
+	v >>= 5;
 
 	while v > 0 {
-		res.push((v & 0xFF) as u8);
-		v = v >> 8;
+		output.push((v & 0xFF) as u8);
+		v >>= 8;
 		byte_counter += 1;
 	}
-	res[1] = res[1] | (byte_counter << 5);
-	res
+	output[start + 1] |= byte_counter << 5;
 }
 
 /// Decodes an `usize`` from the hybrid continuation bit and 3-bit length prefix encoding
@@ -72,7 +118,7 @@ pub fn decompress_usize(bytes: &[u8]) -> Result<usize> {
 	}
 
 	let second_byte = bytes[1];
-	let len: usize = (usize::from(second_byte) & usize::from(0b11100000 as u8)) >> 5;
+	let len: usize = (usize::from(second_byte) & usize::from(0b11100000_u8)) >> 5;
 
 	if bytes.len() < (len + 2) {
 		return Err(BinaryError::InvalidLength {
@@ -81,17 +127,122 @@ pub fn decompress_usize(bytes: &[u8]) -> Result<usize> {
 		});
 	}
 
-	let mut v: usize = (usize::from(first_byte) & usize::from(0b01111111 as u8))
-		| ((usize::from(second_byte) & usize::from(0b00011111 as u8)) << 7);
+	let mut v: usize = (usize::from(first_byte) & usize::from(0b01111111_u8))
+		| ((usize::from(second_byte) & usize::from(0b00011111_u8)) << 7);
 
 	if len == 0 {
-		return Ok(v.clone());
+		return Ok(v);
 	}
 
 	for i in 0..(len as u8) {
-		v = v | (usize::from(bytes[(i + 2) as usize]) << (12 + i * 8));
+		v |= usize::from(bytes[(i + 2) as usize]) << (12 + i * 8);
+	}
+	Ok(v)
+}
+
+/// Encodes an `usize` using the same scheme as `compress_usize`, but with the most significant
+/// bits first.
+///
+/// The first two bytes keep the continuation bit and the 3-bit length in the same place, so
+/// `compressed_usize_len` works on either ordering. They hold the top 12 bits of the value,
+/// followed by the remaining bytes in Big Endian order.
+pub fn compress_usize_be(value: usize) -> Vec<u8> {
+	let mut res: Vec<u8> = Vec::new();
+	compress_usize_be_into(value, &mut res);
+	res
+}
+
+/// Appends an `usize` to `output` in the same form as `compress_usize_be`, without allocating a
+/// buffer for it.
+pub fn compress_usize_be_into(value: usize, output: &mut Vec<u8>) {
+	if value <= 0b01111111 {
+		output.push(value as u8);
+		return;
+	}
+
+	let mut len: usize = 0;
+	while len < size_of::<usize>() && (value >> 12 >> (len * 8)) > 0 {
+		len += 1;
+	}
+	let head = value >> (len * 8);
+	output.push((0b10000000 | (head >> 5)) as u8);
+	output.push(((len << 5) | (head & 0b00011111)) as u8);
+	output.extend_from_slice(&value.to_be_bytes()[size_of::<usize>() - len..]);
+}
+
+/// Decodes an `usize` from the most significant first form written by `compress_usize_be`
+pub fn decompress_usize_be(bytes: &[u8]) -> Result<usize> {
+	let total = compressed_usize_len(bytes).map_err(|_| BinaryError::InvalidLength {
+		actual: bytes.len(),
+		expected: size_of::<usize>(),
+	})?;
+	if bytes.len() < total {
+		return Err(BinaryError::InvalidLength {
+			actual: bytes.len(),
+			expected: size_of::<usize>(),
+		});
+	}
+	if total == 1 {
+		return Ok(usize::from(bytes[0]));
+	}
+
+	let mut v: usize =
+		(usize::from(bytes[0] & 0b01111111) << 5) | usize::from(bytes[1] & 0b00011111);
+	for byte in &bytes[2..total] {
+		v = v.checked_mul(0x100).ok_or(BinaryError::InvalidBytes)? | usize::from(*byte);
+	}
+	Ok(v)
+}
+
+/// Encodes an `usize` with `compress_usize` or `compress_usize_be`, depending on the format
+pub fn compress_usize_as(value: usize, format: ByteFormat) -> Vec<u8> {
+	if format.is_big_endian() {
+		compress_usize_be(value)
+	} else {
+		compress_usize(value)
 	}
-	Ok(v.clone())
+}
+
+/// Appends an `usize` with `compress_usize_into` or `compress_usize_be_into`, depending on the
+/// format
+pub fn compress_usize_as_into(value: usize, format: ByteFormat, output: &mut Vec<u8>) {
+	if format.is_big_endian() {
+		compress_usize_be_into(value, output)
+	} else {
+		compress_usize_into(value, output)
+	}
+}
+
+/// Decodes an `usize` with `decompress_usize` or `decompress_usize_be`, depending on the format
+pub fn decompress_usize_as(bytes: &[u8], format: ByteFormat) -> Result<usize> {
+	if format.is_big_endian() {
+		decompress_usize_be(bytes)
+	} else {
+		decompress_usize(bytes)
+	}
+}
+
+/// Returns the number of bytes used by the compressed `usize` at the start of `bytes`
+pub fn compressed_usize_len(bytes: &[u8]) -> Result<usize> {
+	match bytes {
+		[] => Err(BinaryError::UnexpectedEndOfInput),
+		[first, ..] if first & 0b10000000 == 0 => Ok(1),
+		[_] => Err(BinaryError::UnexpectedEndOfInput),
+		[_, second, ..] => Ok(2 + usize::from((second & 0b11100000) >> 5)),
+	}
+}
+
+/// Hashes a type name and its field names into a schema fingerprint, using 64 bit FNV-1a.
+pub fn fingerprint<'a>(names: impl IntoIterator<Item = &'a str>) -> u64 {
+	let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+	for name in names {
+		// Terminate each name so that ["ab", "c"] and ["a", "bc"] differ
+		for byte in name.bytes().chain([0x00]) {
+			hash ^= u64::from(byte);
+			hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+		}
+	}
+	hash
 }
 
 /// These tests validate that the expected values have not been changed to preserve compatability
@@ -99,8 +250,11 @@ pub fn decompress_usize(bytes: &[u8]) -> Result<usize> {
 mod tests {
 
 	use crate::serde_binary_adv::common::{
-		compress_usize, decompress_usize,
+		ByteFormat, compress_usize, compress_usize_as, compress_usize_as_into, compress_usize_be,
+		compressed_usize_len, decompress_usize, decompress_usize_as, decompress_usize_be,
+		fingerprint,
 		flags::{NONE, NONUNIT_VARIANT, SOME, STRUCT_VARIANT, UNIT_VARIANT},
+		tags,
 	};
 
 	/// These tests validate that the expected values have not been changed to preserve compatability
@@ -113,6 +267,35 @@ mod tests {
 		assert_eq!(STRUCT_VARIANT, 0xFC);
 	}
 
+	#[test]
+	fn test_tags() {
+		let all = [
+			tags::UNIT,
+			tags::BOOL,
+			tags::U8,
+			tags::U16,
+			tags::U32,
+			tags::U64,
+			tags::U128,
+			tags::I8,
+			tags::I16,
+			tags::I32,
+			tags::I64,
+			tags::I128,
+			tags::F32,
+			tags::F64,
+			tags::CHAR,
+			tags::STR,
+			tags::BYTES,
+			tags::SEQ,
+			tags::MAP,
+			tags::NEWTYPE,
+		];
+		for (i, tag) in all.iter().enumerate() {
+			assert_eq!(usize::from(*tag), i + 1);
+		}
+	}
+
 	#[test]
 	fn test_usize_compress_min() {
 		test_usize(0x00);
@@ -180,6 +363,72 @@ mod tests {
 		assert!(decompress_usize(&[0xFF, 0xFF]).is_err());
 	}
 
+	#[test]
+	fn test_compressed_usize_len() {
+		for v in [0x00, 0x7F, 0x80, 0xFFF, 0x1000, 0xFFFFFFFF, usize::MAX] {
+			let encoded = compress_usize(v);
+			assert_eq!(compressed_usize_len(&encoded).unwrap(), encoded.len());
+		}
+		assert!(compressed_usize_len(&[]).is_err());
+		assert!(compressed_usize_len(&[0x80]).is_err());
+	}
+
+	#[test]
+	fn test_varint_byte_order() {
+		assert_eq!(compress_usize(0x1234), vec![0xB4, 0x24, 0x01]);
+		assert_eq!(compress_usize_be(0x1234), vec![0x80, 0x32, 0x34]);
+		assert_eq!(compress_usize_be(0x7F), vec![0x7F]);
+		assert_eq!(compress_usize_be(0xFFF), vec![0xFF, 0x1F]);
+		assert_eq!(
+			compress_usize_as(0x1234, ByteFormat::BigEndian),
+			vec![0x80, 0x32, 0x34]
+		);
+		assert_eq!(
+			decompress_usize_as(&[0xB4, 0x24, 0x01], ByteFormat::LittleEndian).unwrap(),
+			0x1234
+		);
+		for v in [
+			0x00,
+			0x7F,
+			0x80,
+			0xFFF,
+			0x1000,
+			0x123456,
+			0xFFFFFFFF,
+			0x1000000000,
+			usize::MAX,
+		] {
+			let encoded = compress_usize_be(v);
+			assert_eq!(compressed_usize_len(&encoded).unwrap(), encoded.len());
+			assert_eq!(compress_usize(v).len(), encoded.len());
+			assert_eq!(decompress_usize_be(&encoded).unwrap(), v);
+		}
+		assert!(decompress_usize_be(&[]).is_err());
+		assert!(decompress_usize_be(&[0x80, 0x20]).is_err());
+	}
+
+	#[test]
+	fn test_compress_into() {
+		// Appended after what is already there, with the length bits in the right byte
+		for format in [ByteFormat::LittleEndian, ByteFormat::BigEndian] {
+			for v in [0x00, 0x7F, 0x1234, usize::MAX] {
+				let mut output = vec![0x41];
+				compress_usize_as_into(v, format, &mut output);
+				assert_eq!(output[0], 0x41);
+				assert_eq!(output[1..], compress_usize_as(v, format));
+			}
+		}
+	}
+
+	#[test]
+	fn test_fingerprint() {
+		// The FNV-1a offset basis
+		assert_eq!(fingerprint([]), 0xCBF2_9CE4_8422_2325);
+		assert_eq!(fingerprint(["Test", "a"]), fingerprint(["Test", "a"]));
+		assert_ne!(fingerprint(["Test", "a"]), fingerprint(["Test", "b"]));
+		assert_ne!(fingerprint(["ab", "c"]), fingerprint(["a", "bc"]));
+	}
+
 	fn test_usize(value: usize) {
 		let encoded = compress_usize(value);
 		let decoded = decompress_usize(&encoded).unwrap();
@@ -0,0 +1,164 @@
+//! Serialize options of types with a spare value without the `SOME` or `NONE` flag.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::compact_option")]` on an `Option` field whose
+//! contents implement `Nullable`. Options are normally written as a flag followed by the contents;
+//! this writes the contents alone, with a value that the type can never hold standing in for
+//! `None`. For example, an `Option<NonZeroU32>` is written as a `u32` in four bytes rather than
+//! five, with `0` for `None`. `None` then takes as many bytes as `Some`, rather than one, so this
+//! suits fields that are usually set.
+//!
+//! Types such as `String` and `Vec` are deliberately not `Nullable`. Their empty value is also a
+//! valid one, so `Some(String::new())` would be read back as `None`; using this module on them is
+//! rejected at compile time.
+
+use std::num::{
+	NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroU8, NonZeroU16, NonZeroU32,
+	NonZeroU64, NonZeroU128,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A type with a spare value that can represent `None`, since the type can never hold it
+pub trait Nullable: Sized {
+	/// The type written in place of the value
+	type Raw: Serialize + for<'de> Deserialize<'de>;
+
+	/// The value written for `None`
+	const NULL: Self::Raw;
+
+	/// Converts the value into the type written
+	fn to_raw(&self) -> Self::Raw;
+
+	/// Converts the value read back, returning `None` for the null value
+	fn from_raw(raw: Self::Raw) -> Option<Self>;
+}
+
+macro_rules! impl_nullable {
+	($($ty:ty => $raw:ty),*) => {
+		$(
+			impl Nullable for $ty {
+				type Raw = $raw;
+
+				const NULL: $raw = 0;
+
+				fn to_raw(&self) -> $raw {
+					self.get()
+				}
+
+				fn from_raw(raw: $raw) -> Option<Self> {
+					Self::new(raw)
+				}
+			}
+		)*
+	};
+}
+
+impl_nullable!(
+	NonZeroU8 => u8,
+	NonZeroU16 => u16,
+	NonZeroU32 => u32,
+	NonZeroU64 => u64,
+	NonZeroU128 => u128,
+	NonZeroI8 => i8,
+	NonZeroI16 => i16,
+	NonZeroI32 => i32,
+	NonZeroI64 => i64,
+	NonZeroI128 => i128
+);
+
+/// Serializes an option as its contents, or the null value for `None`
+pub fn serialize<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: Nullable,
+{
+	match value {
+		Some(v) => v.to_raw().serialize(serializer),
+		None => T::NULL.serialize(serializer),
+	}
+}
+
+/// Deserializes an option from its contents, reading the null value as `None`
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: Nullable,
+{
+	Ok(T::from_raw(T::Raw::deserialize(deserializer)?))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::num::{NonZeroI16, NonZeroU32};
+
+	use serde::{Deserialize, Serialize};
+	use static_assertions::assert_not_impl_any;
+
+	use super::Nullable;
+	use crate::{Config, Deserializer, Serializer};
+
+	// An empty string or sequence could not be told apart from `None`
+	assert_not_impl_any!(String: Nullable);
+	assert_not_impl_any!(Vec<u8>: Nullable);
+	assert_not_impl_any!(u32: Nullable);
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Reading {
+		#[serde(with = "crate::compact_option")]
+		pub id: Option<NonZeroU32>,
+		#[serde(with = "crate::compact_option")]
+		pub offset: Option<NonZeroI16>,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct PlainReading {
+		pub id: Option<NonZeroU32>,
+		pub offset: Option<NonZeroI16>,
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let value = Reading {
+			id: NonZeroU32::new(0x41),
+			offset: None,
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Struct length, then each field without a flag
+		assert_eq!(serialized, vec![0x02, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00]);
+		// Each field that is `Some` saves its flag byte
+		let value = Reading {
+			id: NonZeroU32::new(0x41),
+			offset: NonZeroI16::new(-1),
+		};
+		let plain = PlainReading {
+			id: value.id,
+			offset: value.offset,
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(
+			serialized.len() + 2,
+			Serializer::to_bytes(&plain, false).unwrap().len()
+		);
+
+		for id in [None, NonZeroU32::new(u32::MAX)] {
+			for offset in [None, NonZeroI16::new(-1), NonZeroI16::new(i16::MIN)] {
+				let value = Reading { id, offset };
+				for big_endian in [false, true] {
+					let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+					let deserialized: Reading =
+						Deserializer::from_bytes(&serialized, big_endian).unwrap();
+					assert_eq!(value, deserialized);
+				}
+			}
+		}
+
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Reading =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+}
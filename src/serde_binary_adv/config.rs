@@ -0,0 +1,106 @@
+//! Settings controlling the binary format.
+
+use std::collections::BTreeMap;
+
+use super::ByteFormat;
+
+/// Settings controlling the binary format written by the Serializer and read by the
+/// Deserializer.
+///
+/// Data must be deserialized with the same settings it was serialized with. The default settings
+/// produce the same format as `Serializer::to_bytes(value, false)`.
+///
+/// Only the in-memory Serializer and Deserializer use these settings; the streaming versions take
+/// the byte order alone.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Config {
+	/// Write multibyte values in Big Endian order, rather than Little Endian
+	pub big_endian: bool,
+	/// Write a presence bitmap before each struct and omit the fields that are `None`
+	///
+	/// Struct variants are not affected.
+	pub bitmap_options: bool,
+	/// The way strings are written
+	pub string_type: StringType,
+	/// Write a schema fingerprint before the value and reject data whose fingerprint differs
+	pub fingerprint: bool,
+	/// The most that may be allocated while deserializing one value, or `None` for no limit
+	pub max_total_alloc: Option<usize>,
+	/// The most values that may be nested inside one another, or `None` for no limit
+	///
+	/// Only self-describing data is checked.
+	pub max_depth: Option<usize>,
+	/// The order of the bytes in compressed lengths
+	///
+	/// Frame headers and the `delta` module always use the default order.
+	pub varint_endianness: ByteFormat,
+	/// Write a type tag before every value, and write structs as maps keyed by field name
+	pub self_describing: bool,
+	/// Write the same bytes for equal values, whatever the order of their map entries
+	pub canonical: bool,
+	/// Write the length in bytes of each map entry before it, so that misread entries are errors
+	///
+	/// Structs and self-describing mode are not affected.
+	pub framed_map_entries: bool,
+	/// Write each distinct string in full only once, and repeats as a reference to the first
+	///
+	/// It cannot be combined with `canonical`.
+	pub intern_strings: bool,
+	/// Leave out the length of tuples and arrays, such as `[u32; 4]`, as their type fixes it
+	///
+	/// Self-describing mode always writes it.
+	pub omit_tuple_lengths: bool,
+	/// The unit variant to read in place of a removed variant, keyed by the name of the enum
+	///
+	/// Only used in self-describing mode, where variants are written by name.
+	pub variant_fallbacks: BTreeMap<String, String>,
+	/// Write the name of each newtype struct before its value and reject data whose name differs
+	pub newtype_names: bool,
+	/// Write the element size of each sequence, so that reading another width is an error
+	///
+	/// Not used in self-describing mode.
+	pub validate_element_size: bool,
+	/// Write floats as decimal text rather than as their IEEE 754 bytes
+	///
+	/// Only used in self-describing mode.
+	pub debug_floats: bool,
+	/// The order the fields of a struct were written in, keyed by the name of the struct
+	///
+	/// Not used in self-describing mode or with `bitmap_options`.
+	pub field_orders: BTreeMap<String, Vec<usize>>,
+}
+
+/// The ways a string can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringType {
+	/// The compressed length followed by the UTF-8 bytes
+	#[default]
+	SizeTagged,
+	/// The compressed length, the UTF-8 bytes and a trailing `0x00`, for readers that expect C
+	/// strings. The length does not include the terminator.
+	SizeTaggedAndNullTerminated,
+	/// The compressed number of UTF-16 code units followed by the code units, in the byte order of
+	/// numbers. Strings written this way are decoded when read, so they cannot be borrowed.
+	Utf16,
+}
+
+impl Config {
+	/// Creates the default settings, with the given byte order
+	pub fn new(big_endian: bool) -> Self {
+		Self {
+			big_endian,
+			..Self::default()
+		}
+	}
+
+	/// Creates the settings for deterministic output, such as for content addressed storage
+	///
+	/// This is the default settings with `canonical` enabled, so data written with them can be
+	/// read with the default settings.
+	pub fn canonical() -> Self {
+		Self {
+			canonical: true,
+			..Self::default()
+		}
+	}
+}
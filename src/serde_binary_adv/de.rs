@@ -1,29 +1,44 @@
 use crate::serde_binary_adv::common::{
-	decompress_usize,
+	compressed_usize_len, decompress_usize, decompress_usize_as, fingerprint,
 	flags::{NONE, NONUNIT_VARIANT, SOME, STRUCT_VARIANT, UNIT_VARIANT},
+	tags,
 };
 
+use std::borrow::Cow;
+use std::str::FromStr;
+
 use super::BinaryError;
+use super::Config;
 use super::Result;
+use super::Schema;
+use super::StringType;
+use super::TypeRegistry;
+use super::ValueType;
 use serde::de::{
-	self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, VariantAccess, Visitor,
+	self, DeserializeSeed, EnumAccess, MapAccess, VariantAccess, Visitor,
+	value::{BorrowedStrDeserializer, CowStrDeserializer},
 };
 use serde::{Deserialize, de::SeqAccess};
 
+// In self-describing mode every value is read through `deserialize_any`, which uses the type tag
+macro_rules! forward_self_describing {
+	($self:ident, $visitor:ident) => {
+		if $self.config.self_describing {
+			return $self.deserialize_any($visitor);
+		}
+	};
+}
+
 macro_rules! impl_deserialize_num {
 	($name:ident, $ty:ty, $visit:ident) => {
 		fn $name<V>(self, visitor: V) -> Result<V::Value>
 		where
 			V: Visitor<'de>,
 		{
-			let bytes: &[u8] = match self.take(size_of::<$ty>()) {
-				Ok(v) => v,
-				Err(e) => {
-					return Err(e);
-				}
-			};
+			forward_self_describing!(self, visitor);
+			let bytes: &[u8] = self.take_num(size_of::<$ty>())?;
 
-			let value: $ty = if self.big_endian {
+			let value: $ty = if self.config.big_endian {
 				<$ty>::from_be_bytes(match bytes.try_into() {
 					Ok(v) => v,
 					Err(e) => {
@@ -51,15 +66,8 @@ macro_rules! impl_deserialize_num {
 macro_rules! impl_next_uxx {
 	($name:ident, $ty:ty) => {
 		fn $name(&mut self) -> Result<$ty> {
-			let bytes = match self.take(size_of::<$ty>()) {
-				Ok(v) => v,
-				Err(e) => {
-					return Err(BinaryError::Message {
-						message: format!("{:?}", e),
-					});
-				}
-			};
-			Ok(if self.big_endian {
+			let bytes = self.take_num(size_of::<$ty>())?;
+			Ok(if self.config.big_endian {
 				<$ty>::from_be_bytes(match bytes.try_into() {
 					Ok(v) => v,
 					Err(e) => {
@@ -86,32 +94,360 @@ macro_rules! impl_next_uxx {
 pub struct Deserializer<'de> {
 	data: &'de [u8],
 	offset: usize,
-	big_endian: bool,
+	config: Config,
+	// The fingerprint read from the data, until it is checked
+	fingerprint: Option<u64>,
+	// The part of the allocation budget that has not been used yet
+	remaining_alloc: Option<usize>,
+	// The strings read so far, when interning strings
+	strings: Vec<Cow<'de, str>>,
+	// Whether reading stopped because the input ended, rather than because it was invalid
+	truncated: bool,
+	// The number of values being read that contain the current one
+	depth: usize,
 }
 
+// The length of the fingerprint written before the value
+const FINGERPRINT_LEN: usize = size_of::<u64>();
+
 impl<'de> Deserializer<'de> {
 	/// Deserializes a vector of bytes (`Vec<u8>`) into Rust structures.
+	///
+	/// Any slice can be read, such as a memory mapped file, and borrowed strings and bytes point
+	/// into it rather than being copied.
 	pub fn from_bytes<'a, T>(data: &'a [u8], big_endian: bool) -> Result<T>
 	where
 		T: Deserialize<'a>,
 	{
+		Self::from_bytes_with_config(data, &Config::new(big_endian))
+	}
+
+	/// Deserializes a vector of bytes (`Vec<u8>`) into Rust structures, using the given settings.
+	pub fn from_bytes_with_config<'a, T>(data: &'a [u8], config: &Config) -> Result<T>
+	where
+		T: Deserialize<'a>,
+	{
+		Deserializer::with_config(data, config.clone()).read_value()
+	}
+
+	// Deserializes a value from the start of `data`, returning it with the number of bytes read,
+	// or `None` if `data` ends before the value does
+	pub(crate) fn from_prefix<'a, T>(data: &'a [u8], config: &Config) -> Result<Option<(T, usize)>>
+	where
+		T: Deserialize<'a>,
+	{
+		let mut deserializer = Deserializer::with_config(data, config.clone());
+		match deserializer.read_value() {
+			Ok(value) => Ok(Some((value, deserializer.offset))),
+			Err(_) if deserializer.truncated => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Deserializes a value preceded by a `u16` type id, as written by
+	/// `Serializer::to_bytes_typed`, into the type registered for the id.
+	pub fn from_bytes_typed<T>(
+		registry: &TypeRegistry<T>,
+		data: &[u8],
+		big_endian: bool,
+	) -> Result<T> {
 		let mut deserializer = Deserializer::new(data, big_endian);
+		let id = u16::deserialize(&mut deserializer)?;
+		registry.deserialize(id, &mut deserializer)
+	}
+
+	/// Deserializes a value preceded by a `u32` magic number, as written by
+	/// `Serializer::to_bytes_with_magic`, in whichever byte order the magic number reads as
+	/// `magic`.
+	///
+	/// Returns the value and whether it was read as Big Endian. A magic number that reads the same
+	/// in both byte orders, such as `0x41414141`, cannot tell them apart, so choose one whose
+	/// bytes differ. Returns `BinaryError::Message` if the magic number does not match in either
+	/// byte order.
+	pub fn from_bytes_detect_endian<'a, T>(data: &'a [u8], magic: u32) -> Result<(T, bool)>
+	where
+		T: Deserialize<'a>,
+	{
+		let mut deserializer = Deserializer::new(data, false);
+		let found = deserializer.next_u32()?;
+		let big_endian = if found == magic {
+			false
+		} else if found.swap_bytes() == magic {
+			true
+		} else {
+			return Err(BinaryError::Message {
+				message: format!(
+					"expected the magic number {:#010X} in either byte order, found {:#010X}",
+					magic, found
+				),
+			});
+		};
+		deserializer.config.big_endian = big_endian;
+		Ok((deserializer.read_value()?, big_endian))
+	}
 
-		let t = T::deserialize(&mut deserializer)?;
+	/// Deserializes a struct after checking that each of its fields has the type given in the
+	/// schema, to find where data differs from what is expected.
+	///
+	/// This requires self-describing mode, where each value is written with its type. A field of
+	/// the wrong type, or a field in the schema that the data does not have, returns a
+	/// `BinaryError::Message` naming the field. Fields that are not in the schema are not checked.
+	pub fn from_bytes_validated<'a, T>(
+		data: &'a [u8],
+		schema: &Schema,
+		config: &Config,
+	) -> Result<T>
+	where
+		T: Deserialize<'a>,
+	{
+		if !config.self_describing {
+			return Err(BinaryError::Message {
+				message: String::from("from_bytes_validated requires self-describing mode"),
+			});
+		}
+		Deserializer::with_config(data, config.clone()).validate(schema)?;
+		Self::from_bytes_with_config(data, config)
+	}
+
+	/// Deserializes a length prefixed frame into Rust structures.
+	///
+	/// The declared length is validated against the available bytes before decoding. In strict
+	/// mode the frame must contain exactly the declared number of bytes, and the value must take
+	/// up all of them, so a value such as `()` that reads no bytes only matches an empty frame.
+	/// Otherwise the frame must contain at least that many, and any bytes after the declared
+	/// length, or after the value, are ignored.
+	pub fn from_frame<'a, T>(data: &'a [u8], big_endian: bool, strict: bool) -> Result<T>
+	where
+		T: Deserialize<'a>,
+	{
+		let header_len = compressed_usize_len(data)?;
+		if data.len() < header_len {
+			return Err(BinaryError::UnexpectedEndOfInput);
+		}
+		let declared = decompress_usize(&data[..header_len])?;
+		let available = data.len() - header_len;
+		if available < declared || (strict && available != declared) {
+			return Err(BinaryError::InvalidLength {
+				actual: available,
+				expected: declared,
+			});
+		}
+		let payload = &data[header_len..header_len + declared];
+		let mut deserializer = Deserializer::new(payload, big_endian);
+		let t = deserializer.read_value()?;
+		if strict && deserializer.offset != declared {
+			return Err(BinaryError::InvalidLength {
+				actual: deserializer.offset,
+				expected: declared,
+			});
+		}
 		Ok(t)
 	}
 
 	/// Creates a binary deserializer
 	pub fn new(input: &'de [u8], big_endian: bool) -> Deserializer<'de> {
+		Self::with_config(input, Config::new(big_endian))
+	}
+
+	/// Creates a binary deserializer using the given settings
+	pub fn with_config(input: &'de [u8], config: Config) -> Deserializer<'de> {
 		Deserializer {
 			data: input,
 			offset: 0,
-			big_endian,
+			remaining_alloc: config.max_total_alloc,
+			config,
+			fingerprint: None,
+			strings: Vec::new(),
+			truncated: false,
+			depth: 0,
 		}
 	}
 
-	fn peek(&self) -> Result<u8> {
+	/// Reads a single field of the struct at the current position, skipping over the fields
+	/// before it without deserializing them.
+	///
+	/// This requires self-describing mode, where struct fields are keyed by name. A fingerprint
+	/// before the struct is skipped but not checked. Fields after the one found are not read, so
+	/// the Deserializer is left in the middle of the struct.
+	pub fn read_field<T>(&mut self, field_name: &str) -> Result<T>
+	where
+		T: Deserialize<'de>,
+	{
+		if !self.config.self_describing {
+			return Err(BinaryError::Message {
+				message: String::from("read_field requires self-describing mode"),
+			});
+		}
+		if self.config.fingerprint && self.offset == 0 {
+			self.next_u64()?;
+		}
+		self.expect_tag(tags::MAP)?;
+		let len = self.next_usize()?;
+		for _ in 0..len {
+			self.expect_tag(tags::STR)?;
+			if self.take_str()? == field_name {
+				return T::deserialize(self);
+			}
+			de::IgnoredAny::deserialize(&mut *self)?;
+		}
+		Err(BinaryError::Message {
+			message: format!("missing field `{}`", field_name),
+		})
+	}
+
+	/// Reads a value at the current position and checks that it equals `expected`, such as a
+	/// magic number or other constant at the start of a format.
+	///
+	/// Returns `BinaryError::Message` if the value read is different.
+	pub fn expect<T>(&mut self, expected: T) -> Result<()>
+	where
+		T: Deserialize<'de> + PartialEq + std::fmt::Debug,
+	{
+		let actual = T::deserialize(&mut *self)?;
+		if actual == expected {
+			Ok(())
+		} else {
+			Err(BinaryError::Message {
+				message: format!("expected {:?}, found {:?}", expected, actual),
+			})
+		}
+	}
+
+	/// Reads a compressed length and returns a Deserializer for that many bytes, such as a
+	/// sub-message carried in a byte array, moving this Deserializer past them.
+	///
+	/// The new Deserializer uses the same settings, but cannot read beyond the end of the
+	/// sub-message, and shares nothing else with this one.
+	pub fn sub_deserializer(&mut self) -> Result<Deserializer<'de>> {
+		let len = self.next_usize()?;
+		let data = self.take(len)?;
+		Ok(Deserializer::with_config(data, self.config.clone()))
+	}
+
+	/// Reads the length of a sequence at the current position, such as a `Vec<T>`, and returns an
+	/// iterator that reads one element each time it is advanced.
+	///
+	/// The elements are not collected, so a long sequence can be processed without holding it in
+	/// memory. The iterator ends after the first error.
+	pub fn deserialize_seq_iter<'a, T>(&'a mut self) -> Result<impl Iterator<Item = Result<T>> + 'a>
+	where
+		T: Deserialize<'de> + 'a,
+	{
+		if self.config.self_describing {
+			self.expect_tag(tags::SEQ)?;
+		}
+		let len = self.next_usize()?;
+		let mut series = if self.config.validate_element_size && !self.config.self_describing {
+			let element_size = self.next()?;
+			BinarySeries::with_element_size(self, len, element_size)
+		} else {
+			BinarySeries::new(self, len)
+		};
+		let mut failed = false;
+		Ok(std::iter::from_fn(move || {
+			if failed {
+				return None;
+			}
+			let element = series.next_element().transpose();
+			failed = matches!(element, Some(Err(_)));
+			element
+		}))
+	}
+
+	// Reads a value, with its fingerprint if there is one
+	fn read_value<T>(&mut self) -> Result<T>
+	where
+		T: Deserialize<'de>,
+	{
+		// The fingerprint is written without a type tag, even in self-describing mode
+		if self.config.fingerprint {
+			self.fingerprint = Some(self.next_u64()?);
+		}
+		let t = T::deserialize(&mut *self)?;
+		// A value that is not a struct has the fingerprint of an empty schema
+		self.check_fingerprint(&[])?;
+		Ok(t)
+	}
+
+	// Checks the type of each field of the struct at the current position against the schema
+	fn validate(&mut self, schema: &Schema) -> Result<()> {
+		if self.config.fingerprint {
+			self.next_u64()?;
+		}
+		self.expect_tag(tags::MAP)?;
+		let len = self.next_usize()?;
+		let mut found: Vec<&str> = Vec::new();
+		for _ in 0..len {
+			self.expect_tag(tags::STR)?;
+			let name = self.take_str()?;
+			if let Some((field, expected)) = schema.fields().iter().find(|(n, _)| *n == name) {
+				let actual = ValueType::from_tag(self.peek()?).ok_or(BinaryError::InvalidBytes)?;
+				if actual != *expected {
+					return Err(BinaryError::Message {
+						message: format!("field `{}` is {}, expected {}", name, actual, expected),
+					});
+				}
+				found.push(field);
+			}
+			de::IgnoredAny::deserialize(&mut *self)?;
+		}
+		match schema
+			.fields()
+			.iter()
+			.find(|(n, _)| !found.contains(&n.as_str()))
+		{
+			Some((name, _)) => Err(BinaryError::Message {
+				message: format!("missing field `{}`", name),
+			}),
+			None => Ok(()),
+		}
+	}
+
+	// Takes `len` from the allocation budget, if there is one
+	fn allocate(&mut self, len: usize) -> Result<()> {
+		if let (Some(remaining), Some(limit)) = (self.remaining_alloc, self.config.max_total_alloc)
+		{
+			if len > remaining {
+				return Err(BinaryError::LengthLimitExceeded {
+					actual: (limit - remaining).saturating_add(len),
+					limit,
+				});
+			}
+			self.remaining_alloc = Some(remaining - len);
+		}
+		Ok(())
+	}
+
+	// Reads a value that contains others, one level deeper, if that is within the depth limit
+	fn nested<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+		if let Some(limit) = self.config.max_depth
+			&& self.depth >= limit
+		{
+			return Err(BinaryError::DepthLimitExceeded { limit });
+		}
+		self.depth += 1;
+		let value = read(self);
+		self.depth -= 1;
+		value
+	}
+
+	fn check_fingerprint(&mut self, schema: &[&str]) -> Result<()> {
+		match self.fingerprint.take() {
+			Some(actual) => {
+				let expected = fingerprint(schema.iter().copied());
+				if actual == expected {
+					Ok(())
+				} else {
+					Err(BinaryError::SchemaMismatch { actual, expected })
+				}
+			}
+			None => Ok(()),
+		}
+	}
+
+	fn peek(&mut self) -> Result<u8> {
 		if self.offset >= self.data.len() {
+			self.truncated = true;
 			Err(BinaryError::UnexpectedEndOfInput)
 		} else {
 			Ok(self.data[self.offset])
@@ -120,6 +456,7 @@ impl<'de> Deserializer<'de> {
 
 	fn next(&mut self) -> Result<u8> {
 		if self.offset >= self.data.len() {
+			self.truncated = true;
 			Err(BinaryError::UnexpectedEndOfInput)
 		} else {
 			let byte = self.data[self.offset];
@@ -129,43 +466,191 @@ impl<'de> Deserializer<'de> {
 	}
 
 	fn take(&mut self, len: usize) -> Result<&'de [u8]> {
-		if self.offset + len > self.data.len() {
-			Err(BinaryError::UnexpectedEndOfInput)
-		} else {
-			let slice = &self.data[self.offset..self.offset + len];
-			self.offset += len;
-			Ok(slice)
+		// The length may come from the data, so it can be large enough to overflow
+		match self
+			.offset
+			.checked_add(len)
+			.filter(|end| *end <= self.data.len())
+		{
+			Some(end) => {
+				let slice = &self.data[self.offset..end];
+				self.offset = end;
+				Ok(slice)
+			}
+			None => {
+				self.truncated = true;
+				Err(BinaryError::UnexpectedEndOfInput)
+			}
+		}
+	}
+
+	// Takes the bytes of a number, reporting its width if there are not enough
+	fn take_num(&mut self, len: usize) -> Result<&'de [u8]> {
+		let available = self.data.len() - self.offset;
+		if len > available {
+			self.truncated = true;
+			return Err(BinaryError::InvalidLength {
+				actual: available,
+				expected: len,
+			});
 		}
+		self.take(len)
 	}
 
+	impl_next_uxx!(next_u16, u16);
 	impl_next_uxx!(next_u32, u32);
+	impl_next_uxx!(next_u64, u64);
+	impl_next_uxx!(next_u128, u128);
+	impl_next_uxx!(next_i8, i8);
+	impl_next_uxx!(next_i16, i16);
+	impl_next_uxx!(next_i32, i32);
+	impl_next_uxx!(next_i64, i64);
+	impl_next_uxx!(next_i128, i128);
+	impl_next_uxx!(next_f32, f32);
+	impl_next_uxx!(next_f64, f64);
+
+	fn next_char(&mut self) -> Result<char> {
+		let bytes: &[u8] = match self.peek()? {
+			0x00..=0x7F => self.take(1)?,
+			0xC0..=0xDF => self.take(2)?,
+			0xE0..=0xEF => self.take(3)?,
+			0xF0..=0xFF => self.take(4)?,
+			_ => return Err(BinaryError::InvalidBytes),
+		};
+		let s = match String::from_utf8(bytes.to_vec()) {
+			Ok(v) => v,
+			Err(e) => {
+				return Err(BinaryError::from(e));
+			}
+		};
+		match s.chars().next() {
+			Some(c) => Ok(c),
+			// Because of the prior checks, this should never return None
+			None => Err(BinaryError::Message {
+				message: String::from("failed to decode character"),
+			}),
+		}
+	}
+
+	// Reads a float written as decimal text
+	fn next_float_text<T: FromStr>(&mut self) -> Result<T> {
+		let len = self.next_usize()?;
+		let bytes = self.take(len)?;
+		std::str::from_utf8(bytes)
+			.ok()
+			.and_then(|text| text.parse().ok())
+			.ok_or(BinaryError::InvalidBytes)
+	}
+
+	// Reads a type tag in self-describing mode and checks it is the one expected
+	fn expect_tag(&mut self, expected: u8) -> Result<()> {
+		let actual = self.next()?;
+		if actual == expected {
+			Ok(())
+		} else {
+			Err(BinaryError::MissingOrInvalidFlag { actual, expected })
+		}
+	}
+
+	// Reads the fields of a tuple or struct variant, which are written as a sequence but without
+	// the element size of `validate_element_size`
+	fn read_variant_contents<V>(&mut self, visitor: V) -> Result<V::Value>
+	where
+		V: Visitor<'de>,
+	{
+		if self.config.self_describing {
+			return de::Deserializer::deserialize_any(self, visitor);
+		}
+		let len = self.next_usize()?;
+		self.allocate(len)?;
+		visitor.visit_seq(BinarySeries::new(self, len))
+	}
+
+	// Reads the length of a tuple and checks it is the one expected
+	fn expect_len(&mut self, expected: usize) -> Result<()> {
+		let actual = self.next_usize()?;
+		if actual == expected {
+			Ok(())
+		} else {
+			Err(BinaryError::InvalidLength { actual, expected })
+		}
+	}
 
 	fn next_usize(&mut self) -> Result<usize> {
-		let mut bytes: Vec<u8> = vec![self.next()?];
-		if (bytes[0] & 0b10000000) != 0 {
-			bytes.push(self.next()?);
-			let extra_bytes = (bytes[1] & 0b11100000) >> 5;
-			if extra_bytes > 0 {
-				for _ in 0..extra_bytes {
-					bytes.push(self.next()?);
-				}
+		// Decoded in place, so reading a length does not allocate
+		// Only an incomplete length can fail here
+		let len = compressed_usize_len(&self.data[self.offset..]).inspect_err(|_| {
+			self.truncated = true;
+		})?;
+		let bytes = self.take(len)?;
+		decompress_usize_as(bytes, self.config.varint_endianness)
+	}
+
+	// Reads a string, which is borrowed from the input unless it had to be decoded
+	fn take_str(&mut self) -> Result<Cow<'de, str>> {
+		if !self.config.intern_strings {
+			return self.take_full_str();
+		}
+		match self.next_usize()? {
+			0 => {
+				let s = self.take_full_str()?;
+				self.strings.push(s.clone());
+				Ok(s)
 			}
+			index => match self.strings.get(index - 1) {
+				Some(s) => Ok(s.clone()),
+				None => Err(BinaryError::InvalidBytes),
+			},
 		}
-		Ok(decompress_usize(&bytes)?)
 	}
 
-	fn take_string(&mut self) -> Result<String> {
+	fn take_full_str(&mut self) -> Result<Cow<'de, str>> {
+		if self.config.string_type == StringType::Utf16 {
+			return self.take_utf16().map(Cow::Owned);
+		}
 		let size = self.next_usize()?;
-		match String::from_utf8(self.take(size)?.to_vec()) {
-			Ok(v) => Ok(v),
+		self.allocate(size)?;
+		let bytes = self.take(size)?;
+		if self.config.string_type == StringType::SizeTaggedAndNullTerminated {
+			let terminator = self.next()?;
+			if terminator != 0x00 {
+				return Err(BinaryError::MissingOrInvalidFlag {
+					actual: terminator,
+					expected: 0x00,
+				});
+			}
+		}
+		match std::str::from_utf8(bytes) {
+			Ok(v) => Ok(Cow::Borrowed(v)),
 			Err(e) => Err(BinaryError::Message {
 				message: format!("{:?}", e),
 			}),
 		}
 	}
+
+	fn take_utf16(&mut self) -> Result<String> {
+		let len = self.next_usize()?;
+		let size = len
+			.checked_mul(2)
+			.ok_or(BinaryError::UnexpectedEndOfInput)?;
+		self.allocate(size)?;
+		// The size is twice the length, so there is never a byte left over
+		let (chunks, _) = self.take(size)?.as_chunks::<2>();
+		let units: Vec<u16> = chunks
+			.iter()
+			.map(|v| {
+				if self.config.big_endian {
+					u16::from_be_bytes(*v)
+				} else {
+					u16::from_le_bytes(*v)
+				}
+			})
+			.collect();
+		String::from_utf16(&units).map_err(|_| BinaryError::InvalidBytes)
+	}
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
 	type Error = BinaryError;
 
 	impl_deserialize_num!(deserialize_u16, u16, visit_u16);
@@ -185,6 +670,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		visitor.visit_bool(self.next()? != 0x00)
 	}
 
@@ -192,6 +678,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		// Single bytes are checked against their tag, so that a wider number read as a byte is
+		// rejected rather than narrowed
+		if self.config.self_describing {
+			self.expect_tag(tags::I8)?;
+		}
 		visitor.visit_i8(self.next()? as i8)
 	}
 
@@ -199,6 +690,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		if self.config.self_describing {
+			self.expect_tag(tags::U8)?;
+		}
 		visitor.visit_u8(self.next()?)
 	}
 
@@ -206,44 +700,16 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
-		let bytes: &[u8];
-		match self.peek()? {
-			0x00..=0x7F => {
-				bytes = self.take(1)?;
-			}
-			0xC0..=0xDF => {
-				bytes = self.take(2)?;
-			}
-			0xE0..=0xEF => {
-				bytes = self.take(3)?;
-			}
-			0xF0..=0xFF => bytes = self.take(4)?,
-			_ => return Err(BinaryError::InvalidBytes),
-		}
-		let s = match String::from_utf8(bytes.to_vec()) {
-			Ok(v) => v,
-			Err(e) => {
-				return Err(BinaryError::from(e));
-			}
-		};
-		let ch = match s.chars().next() {
-			Some(c) => c,
-			None => {
-				// Because of the prior checks, this should never return None
-				return Err(BinaryError::Message {
-					message: String::from("failed to decode character"),
-				});
-			}
-		};
-		visitor.visit_char(ch)
+		forward_self_describing!(self, visitor);
+		visitor.visit_char(self.next_char()?)
 	}
 
 	fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		let s = self.take_string()?;
-		visitor.visit_str(&s.as_str())
+		forward_self_describing!(self, visitor);
+		visit_str(visitor, self.take_str()?)
 	}
 
 	fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -257,16 +723,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		let len = self.next_usize()?;
+		self.allocate(len)?;
 		let bytes = self.take(len)?;
-		visitor.visit_bytes(bytes)
+		visitor.visit_borrowed_bytes(bytes)
 	}
 
 	fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		let len = self.next_usize()?;
+		self.allocate(len)?;
 		let bytes = self.take(len)?;
 		visitor.visit_byte_buf(bytes.to_vec())
 	}
@@ -281,10 +751,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		} else if flag == SOME {
 			visitor.visit_some(self)
 		} else {
-			Err(BinaryError::MissingOrInvalidFlag {
-				actual: flag,
-				expected: SOME,
-			})
+			Err(BinaryError::InvalidOptionFlag { actual: flag })
 		}
 	}
 
@@ -292,6 +759,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		visitor.visit_unit()
 	}
 
@@ -300,13 +768,26 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		visitor.visit_unit()
 	}
 
-	fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+	fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
+		if self.config.newtype_names {
+			if self.config.self_describing {
+				self.expect_tag(tags::NEWTYPE)?;
+				self.expect_tag(tags::STR)?;
+			}
+			let actual = self.take_str()?;
+			if actual != name {
+				return Err(BinaryError::Message {
+					message: format!("expected newtype struct {}, found {}", name, actual),
+				});
+			}
+		}
 		visitor.visit_newtype_struct(self)
 	}
 
@@ -314,69 +795,137 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		let len: usize = self.next_usize()?;
+		self.allocate(len)?;
+		if self.config.validate_element_size {
+			let element_size = self.next()?;
+			let series = BinarySeries::with_element_size(&mut *self, len, element_size);
+			return visitor.visit_seq(series);
+		}
 		visitor.visit_seq(BinarySeries::new(&mut *self, len))
 	}
 
-	fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+	fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		self.deserialize_seq(visitor)
+		forward_self_describing!(self, visitor);
+		if !self.config.omit_tuple_lengths {
+			self.expect_len(len)?;
+		}
+		visitor.visit_seq(BinarySeries::new(&mut *self, len))
 	}
 
 	fn deserialize_tuple_struct<V>(
 		self,
-		_name: &'static str,
-		_len: usize,
+		name: &'static str,
+		len: usize,
 		visitor: V,
 	) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		self.deserialize_seq(visitor)
+		if self.fingerprint.is_some() && self.offset == FINGERPRINT_LEN {
+			self.check_fingerprint(&[name])?;
+		}
+		// Tuple structs are always written with their length, unlike tuples
+		forward_self_describing!(self, visitor);
+		self.expect_len(len)?;
+		visitor.visit_seq(BinarySeries::new(&mut *self, len))
 	}
 
 	fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		let len: usize = self.next_usize()?;
+		self.allocate(len)?;
 		visitor.visit_map(BinarySeries::new(self, len))
 	}
 
 	fn deserialize_struct<V>(
 		self,
-		_name: &'static str,
-		_fields: &'static [&'static str],
+		name: &'static str,
+		fields: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
+		// Only the top level struct, which starts right after the fingerprint, is part of it
+		if self.fingerprint.is_some() && self.offset == FINGERPRINT_LEN {
+			let mut schema = vec![name];
+			schema.extend_from_slice(fields);
+			self.check_fingerprint(&schema)?;
+		}
+		// In self-describing mode fields are read by name, so unknown fields are skipped
+		forward_self_describing!(self, visitor);
 		let len = self.next_usize()?;
-		visitor.visit_seq(BinarySeries::new(&mut *self, len))
+		// Extra fields cannot be skipped without knowing their types, and reading on would
+		// misinterpret their bytes as the values that follow
+		if len > fields.len() {
+			return Err(BinaryError::InvalidLength {
+				actual: len,
+				expected: fields.len(),
+			});
+		}
+		if self.config.bitmap_options {
+			let bitmap = self.take(len.div_ceil(8))?;
+			visitor.visit_seq(BinarySeries::with_bitmap(&mut *self, len, bitmap))
+		} else if let Some(order) = self.config.field_orders.get(name) {
+			if order.len() != len {
+				return Err(BinaryError::InvalidLength {
+					actual: len,
+					expected: order.len(),
+				});
+			}
+			let order = order.clone();
+			visitor.visit_map(FieldOrder::new(&mut *self, fields, order)?)
+		} else {
+			visitor.visit_seq(BinarySeries::new(&mut *self, len))
+		}
 	}
 
 	fn deserialize_enum<V>(
 		self,
-		_name: &'static str,
+		name: &'static str,
 		variants: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
+		// An enum without variants has no values, so there is nothing that could be read
+		if variants.is_empty() {
+			return Err(BinaryError::Message {
+				message: format!("enum {} has no variants", name),
+			});
+		}
 		let variant_type = self.next()?;
 
 		match variant_type {
-			NONUNIT_VARIANT => visitor.visit_enum(Enum::new(self)),
-			STRUCT_VARIANT => visitor.visit_enum(Enum::new(self)),
-			UNIT_VARIANT => {
-				let variant_index: u32 = self.next_u32()?;
-				let variant: &'de str = variants[variant_index as usize];
-
-				visitor.visit_enum(variant.into_deserializer())
+			UNIT_VARIANT | NONUNIT_VARIANT | STRUCT_VARIANT
+				if self.config.self_describing
+					&& self.config.variant_fallbacks.contains_key(name) =>
+			{
+				self.expect_tag(tags::STR)?;
+				let variant = self.take_str()?;
+				if variants.contains(&variant.as_ref()) {
+					return visitor.visit_enum(Enum::with_name(self, variant_type, variant));
+				}
+				// Read the fallback as a unit variant, in place of the unknown one
+				if variant_type != UNIT_VARIANT {
+					de::IgnoredAny::deserialize(&mut *self)?;
+				}
+				let fallback = Cow::Owned(self.config.variant_fallbacks[name].clone());
+				visitor.visit_enum(Enum::with_name(self, UNIT_VARIANT, fallback))
+			}
+			// The variant index is passed to the visitor as is, rather than looked up in
+			// `variants`, so that `#[serde(other)]` can handle indexes beyond the known set
+			UNIT_VARIANT | NONUNIT_VARIANT | STRUCT_VARIANT => {
+				visitor.visit_enum(Enum::new(self, variant_type))
 			}
 			_ => Err(BinaryError::MissingOrInvalidFlag {
 				actual: variant_type,
@@ -389,21 +938,102 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	where
 		V: Visitor<'de>,
 	{
+		forward_self_describing!(self, visitor);
 		visitor.visit_u32(self.next_u32()?)
 	}
 
-	fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+	fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		unimplemented!()
+		// Skipping a value requires knowing its type, so this is the same as deserialize_any
+		self.deserialize_any(visitor)
 	}
 
-	fn deserialize_any<V>(self, _visitor: V) -> std::result::Result<V::Value, Self::Error>
+	fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
 	where
 		V: Visitor<'de>,
 	{
-		unimplemented!()
+		if !self.config.self_describing {
+			return Err(BinaryError::Message {
+				message: String::from(
+					"deserialize_any requires self-describing mode; types such as internally \
+					 tagged or untagged enums cannot be read from positional data",
+				),
+			});
+		}
+		match self.next()? {
+			tags::UNIT => visitor.visit_unit(),
+			tags::BOOL => visitor.visit_bool(self.next()? != 0x00),
+			tags::U8 => visitor.visit_u8(self.next()?),
+			tags::U16 => visitor.visit_u16(self.next_u16()?),
+			tags::U32 => visitor.visit_u32(self.next_u32()?),
+			tags::U64 => visitor.visit_u64(self.next_u64()?),
+			tags::U128 => visitor.visit_u128(self.next_u128()?),
+			tags::I8 => visitor.visit_i8(self.next_i8()?),
+			tags::I16 => visitor.visit_i16(self.next_i16()?),
+			tags::I32 => visitor.visit_i32(self.next_i32()?),
+			tags::I64 => visitor.visit_i64(self.next_i64()?),
+			tags::I128 => visitor.visit_i128(self.next_i128()?),
+			tags::F32 if self.config.debug_floats => visitor.visit_f32(self.next_float_text()?),
+			tags::F64 if self.config.debug_floats => visitor.visit_f64(self.next_float_text()?),
+			tags::F32 => visitor.visit_f32(self.next_f32()?),
+			tags::F64 => visitor.visit_f64(self.next_f64()?),
+			tags::CHAR => visitor.visit_char(self.next_char()?),
+			tags::STR => visit_str(visitor, self.take_str()?),
+			tags::BYTES => {
+				let len = self.next_usize()?;
+				self.allocate(len)?;
+				visitor.visit_borrowed_bytes(self.take(len)?)
+			}
+			NONE => visitor.visit_none(),
+			SOME => self.nested(|de| visitor.visit_some(de)),
+			tags::SEQ => {
+				let len = self.next_usize()?;
+				self.allocate(len)?;
+				self.nested(|de| visitor.visit_seq(BinarySeries::new(de, len)))
+			}
+			tags::MAP => {
+				let len = self.next_usize()?;
+				self.allocate(len)?;
+				self.nested(|de| visitor.visit_map(BinarySeries::new(de, len)))
+			}
+			// The name is only there to help when debugging, so it is not checked
+			tags::NEWTYPE => {
+				self.expect_tag(tags::STR)?;
+				self.take_str()?;
+				self.nested(|de| visitor.visit_newtype_struct(de))
+			}
+			// Variants are read the way they would be written in a human readable format: the
+			// name for a unit variant, otherwise a map from the name to the contents
+			UNIT_VARIANT => {
+				self.expect_tag(tags::STR)?;
+				visit_str(visitor, self.take_str()?)
+			}
+			NONUNIT_VARIANT | STRUCT_VARIANT => {
+				self.expect_tag(tags::STR)?;
+				let name = self.take_str()?;
+				self.nested(|de| visitor.visit_map(VariantMap::new(de, name)))
+			}
+			_ => Err(BinaryError::InvalidBytes),
+		}
+	}
+
+	// The binary format is not human readable, so types such as `Uuid` and `IpAddr` use their
+	// compact forms
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
+
+// Passes a string to the visitor, borrowed from the input where possible
+fn visit_str<'de, V>(visitor: V, value: Cow<'de, str>) -> Result<V::Value>
+where
+	V: Visitor<'de>,
+{
+	match value {
+		Cow::Borrowed(v) => visitor.visit_borrowed_str(v),
+		Cow::Owned(v) => visitor.visit_string(v),
 	}
 }
 
@@ -411,6 +1041,11 @@ struct BinarySeries<'a, 'de: 'a> {
 	de: &'a mut Deserializer<'de>,
 	len: usize,
 	position: usize,
+	bitmap: Option<&'de [u8]>,
+	// The size of the first element when it is a number, or 0 when it is not checked
+	element_size: u8,
+	// The start and length of the map entry being read, when entries are written with lengths
+	entry: Option<(usize, usize)>,
 }
 
 impl<'a, 'de> BinarySeries<'a, 'de> {
@@ -419,8 +1054,41 @@ impl<'a, 'de> BinarySeries<'a, 'de> {
 			de,
 			len,
 			position: 0,
+			bitmap: None,
+			element_size: 0,
+			entry: None,
+		}
+	}
+
+	pub fn with_element_size(de: &'a mut Deserializer<'de>, len: usize, element_size: u8) -> Self {
+		Self {
+			de,
+			len,
+			position: 0,
+			bitmap: None,
+			element_size,
+			entry: None,
+		}
+	}
+
+	pub fn with_bitmap(de: &'a mut Deserializer<'de>, len: usize, bitmap: &'de [u8]) -> Self {
+		Self {
+			de,
+			len,
+			position: 0,
+			bitmap: Some(bitmap),
+			element_size: 0,
+			entry: None,
 		}
 	}
+
+	// The number of entries left, for pre-sizing the collection being read. Almost every entry
+	// takes at least one byte, so the hint is bounded by the input left rather than trusting a
+	// length that hostile input could make huge.
+	fn remaining(&self) -> usize {
+		let len = self.len.saturating_sub(self.position);
+		len.min(self.de.data.len() - self.de.offset)
+	}
 }
 
 impl<'de, 'a> SeqAccess<'de> for BinarySeries<'a, 'de> {
@@ -442,7 +1110,33 @@ impl<'de, 'a> SeqAccess<'de> for BinarySeries<'a, 'de> {
 				expected: self.len,
 			});
 		}
-		seed.deserialize(&mut *self.de).map(Some)
+		match self.bitmap {
+			Some(bitmap) => {
+				let field = self.position - 1;
+				let present = bitmap[field / 8] & (1 << (field % 8)) != 0;
+				seed.deserialize(FieldDeserializer::new(&mut *self.de, present))
+					.map(Some)
+			}
+			None if self.position == 1 && self.element_size != 0 => {
+				let start = self.de.offset;
+				let value = seed.deserialize(&mut *self.de)?;
+				let read = self.de.offset - start;
+				if read != usize::from(self.element_size) {
+					return Err(BinaryError::Message {
+						message: format!(
+							"sequence elements were written {} bytes wide, but read as {}",
+							self.element_size, read
+						),
+					});
+				}
+				Ok(Some(value))
+			}
+			None => seed.deserialize(&mut *self.de).map(Some),
+		}
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.remaining())
 	}
 }
 
@@ -462,9 +1156,64 @@ impl<'de, 'a> MapAccess<'de> for BinarySeries<'a, 'de> {
 				expected: self.len,
 			});
 		}
+		if self.de.config.framed_map_entries && !self.de.config.self_describing {
+			let len = self.de.next_usize()?;
+			self.entry = Some((self.de.offset, len));
+		}
 		seed.deserialize(&mut *self.de).map(Some)
 	}
 
+	fn next_value_seed<V>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error>
+	where
+		V: de::DeserializeSeed<'de>,
+	{
+		let value = seed.deserialize(&mut *self.de)?;
+		if let Some((start, len)) = self.entry.take() {
+			let read = self.de.offset - start;
+			if read != len {
+				return Err(BinaryError::InvalidLength {
+					actual: read,
+					expected: len,
+				});
+			}
+		}
+		Ok(value)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.remaining())
+	}
+}
+
+/// Presents an enum variant with contents as a map with a single entry, from the variant name to
+/// the contents
+struct VariantMap<'a, 'de: 'a> {
+	de: &'a mut Deserializer<'de>,
+	name: Option<Cow<'de, str>>,
+}
+
+impl<'a, 'de> VariantMap<'a, 'de> {
+	fn new(de: &'a mut Deserializer<'de>, name: Cow<'de, str>) -> Self {
+		Self {
+			de,
+			name: Some(name),
+		}
+	}
+}
+
+impl<'de, 'a> MapAccess<'de> for VariantMap<'a, 'de> {
+	type Error = BinaryError;
+
+	fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+	where
+		K: de::DeserializeSeed<'de>,
+	{
+		match self.name.take() {
+			Some(name) => seed.deserialize(CowStrDeserializer::new(name)).map(Some),
+			None => Ok(None),
+		}
+	}
+
 	fn next_value_seed<V>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error>
 	where
 		V: de::DeserializeSeed<'de>,
@@ -473,13 +1222,94 @@ impl<'de, 'a> MapAccess<'de> for BinarySeries<'a, 'de> {
 	}
 }
 
+/// Presents the fields of a struct written in another order as a map from field name to value, so
+/// that each value is read into the field it was written for
+struct FieldOrder<'a, 'de: 'a> {
+	de: &'a mut Deserializer<'de>,
+	names: std::vec::IntoIter<&'static str>,
+}
+
+impl<'a, 'de> FieldOrder<'a, 'de> {
+	fn new(
+		de: &'a mut Deserializer<'de>,
+		fields: &'static [&'static str],
+		order: Vec<usize>,
+	) -> Result<Self> {
+		let names = order
+			.into_iter()
+			.map(|i| {
+				fields.get(i).copied().ok_or_else(|| BinaryError::Message {
+					message: format!("field {} is out of range for {} fields", i, fields.len()),
+				})
+			})
+			.collect::<Result<Vec<&'static str>>>()?;
+		Ok(Self {
+			de,
+			names: names.into_iter(),
+		})
+	}
+}
+
+impl<'de, 'a> MapAccess<'de> for FieldOrder<'a, 'de> {
+	type Error = BinaryError;
+
+	fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+	where
+		K: de::DeserializeSeed<'de>,
+	{
+		match self.names.next() {
+			Some(name) => seed
+				.deserialize(BorrowedStrDeserializer::new(name))
+				.map(Some),
+			None => Ok(None),
+		}
+	}
+
+	fn next_value_seed<V>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error>
+	where
+		V: de::DeserializeSeed<'de>,
+	{
+		seed.deserialize(&mut *self.de)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.names.len())
+	}
+}
+
 struct Enum<'a, 'de: 'a> {
 	de: &'a mut Deserializer<'de>,
+	variant_type: u8,
+	// The variant name, when it has already been read
+	name: Option<Cow<'de, str>>,
 }
 
 impl<'a, 'de> Enum<'a, 'de> {
-	fn new(de: &'a mut Deserializer<'de>) -> Self {
-		Enum { de }
+	fn new(de: &'a mut Deserializer<'de>, variant_type: u8) -> Self {
+		Enum {
+			de,
+			variant_type,
+			name: None,
+		}
+	}
+
+	fn with_name(de: &'a mut Deserializer<'de>, variant_type: u8, name: Cow<'de, str>) -> Self {
+		Enum {
+			de,
+			variant_type,
+			name: Some(name),
+		}
+	}
+
+	fn expect(&self, expected: u8) -> Result<()> {
+		if self.variant_type == expected {
+			Ok(())
+		} else {
+			Err(BinaryError::MissingOrInvalidFlag {
+				actual: self.variant_type,
+				expected,
+			})
+		}
 	}
 }
 
@@ -487,11 +1317,15 @@ impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
 	type Error = BinaryError;
 	type Variant = Self;
 
-	fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+	fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant)>
 	where
 		V: DeserializeSeed<'de>,
 	{
-		Ok((seed.deserialize(&mut *self.de)?, self))
+		let value = match self.name.take() {
+			Some(name) => seed.deserialize(CowStrDeserializer::<BinaryError>::new(name))?,
+			None => seed.deserialize(&mut *self.de)?,
+		};
+		Ok((value, self))
 	}
 }
 
@@ -499,13 +1333,18 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
 	type Error = BinaryError;
 
 	fn unit_variant(self) -> Result<()> {
-		Err(BinaryError::UnexpectedType)
+		if self.variant_type == UNIT_VARIANT {
+			Ok(())
+		} else {
+			Err(BinaryError::UnexpectedType)
+		}
 	}
 
 	fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
 	where
 		T: DeserializeSeed<'de>,
 	{
+		self.expect(NONUNIT_VARIANT)?;
 		seed.deserialize(self.de)
 	}
 
@@ -513,13 +1352,94 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
 	where
 		V: Visitor<'de>,
 	{
-		de::Deserializer::deserialize_seq(self.de, visitor)
+		self.expect(NONUNIT_VARIANT)?;
+		self.de.read_variant_contents(visitor)
 	}
 
 	fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		de::Deserializer::deserialize_seq(self.de, visitor)
+		self.expect(STRUCT_VARIANT)?;
+		self.de.read_variant_contents(visitor)
+	}
+}
+
+macro_rules! forward_to_inner {
+	($($name:ident($($arg:ident: $ty:ty),*);)*) => {
+		$(
+			fn $name<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+			where
+				V: Visitor<'de>,
+			{
+				self.de.$name($($arg,)* visitor)
+			}
+		)*
+	};
+}
+
+/// Deserializes a single struct field when presence bitmaps are enabled, reading `Option` values
+/// without the `SOME` flag and using the bitmap to decide whether they are present
+struct FieldDeserializer<'a, 'de: 'a> {
+	de: &'a mut Deserializer<'de>,
+	present: bool,
+}
+
+impl<'a, 'de> FieldDeserializer<'a, 'de> {
+	fn new(de: &'a mut Deserializer<'de>, present: bool) -> Self {
+		Self { de, present }
+	}
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldDeserializer<'a, 'de> {
+	type Error = BinaryError;
+
+	forward_to_inner! {
+		deserialize_any();
+		deserialize_bool();
+		deserialize_u8();
+		deserialize_u16();
+		deserialize_u32();
+		deserialize_u64();
+		deserialize_u128();
+		deserialize_i8();
+		deserialize_i16();
+		deserialize_i32();
+		deserialize_i64();
+		deserialize_i128();
+		deserialize_f32();
+		deserialize_f64();
+		deserialize_char();
+		deserialize_str();
+		deserialize_string();
+		deserialize_bytes();
+		deserialize_byte_buf();
+		deserialize_unit();
+		deserialize_unit_struct(name: &'static str);
+		deserialize_newtype_struct(name: &'static str);
+		deserialize_seq();
+		deserialize_tuple(len: usize);
+		deserialize_tuple_struct(name: &'static str, len: usize);
+		deserialize_map();
+		deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+		deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+		deserialize_identifier();
+		deserialize_ignored_any();
+	}
+
+	fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+	where
+		V: Visitor<'de>,
+	{
+		if self.present {
+			visitor.visit_some(self.de)
+		} else {
+			visitor.visit_none()
+		}
+	}
+
+	// Fields must be read in the same form as other values
+	fn is_human_readable(&self) -> bool {
+		false
 	}
 }
@@ -0,0 +1,188 @@
+//! Serialize sorted integer sequences using delta encoding.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::delta")]` on a `Vec<u32>` or `Vec<u64>` field. The
+//! sequence is written as a byte array holding the element count followed by the compressed
+//! difference between each element and the one before it (the first element is stored as its
+//! difference from zero). Ascending sequences of close values, such as sorted indexes, shrink to
+//! one or two bytes per element.
+//!
+//! Like any byte array, it is preceded by its length in bytes, so the layout is the blob length,
+//! then the count, then the deltas, rather than the count directly followed by the deltas.
+//!
+//! The sequence must be in ascending order; serializing a descending pair is an error.
+
+use std::fmt;
+
+use serde::{
+	Deserializer, Serializer,
+	de::{self, Visitor},
+	ser,
+};
+
+use crate::serde_binary_adv::common::{compress_usize, compressed_usize_len, decompress_usize};
+
+/// An unsigned integer type that can be delta encoded
+pub trait DeltaInteger: Copy + PartialOrd + Into<u64> + TryFrom<u64> {}
+
+impl DeltaInteger for u32 {}
+impl DeltaInteger for u64 {}
+
+/// Serializes an ascending sequence of integers as a count followed by compressed deltas
+pub fn serialize<S, T>(value: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: DeltaInteger,
+{
+	let mut bytes = compress_usize(value.len());
+	let mut previous: u64 = 0;
+	for (i, item) in value.iter().enumerate() {
+		if i > 0 && *item < value[i - 1] {
+			return Err(ser::Error::custom(format!(
+				"delta encoding requires an ascending sequence, element {} is smaller than the \
+				 previous element",
+				i
+			)));
+		}
+		let current: u64 = (*item).into();
+		let delta = usize::try_from(current - previous).map_err(ser::Error::custom)?;
+		bytes.append(&mut compress_usize(delta));
+		previous = current;
+	}
+	serializer.serialize_bytes(&bytes)
+}
+
+/// Deserializes a delta encoded sequence of integers
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: DeltaInteger,
+{
+	deserializer.deserialize_byte_buf(DeltaVisitor::<T>(std::marker::PhantomData))
+}
+
+struct DeltaVisitor<T>(std::marker::PhantomData<T>);
+
+impl<T> DeltaVisitor<T> {
+	fn next_usize<E: de::Error>(bytes: &[u8], offset: &mut usize) -> Result<usize, E> {
+		let remaining = &bytes[*offset..];
+		let len = compressed_usize_len(remaining).map_err(de::Error::custom)?;
+		if remaining.len() < len {
+			return Err(de::Error::invalid_length(
+				bytes.len(),
+				&"a complete delta sequence",
+			));
+		}
+		let value = decompress_usize(&remaining[..len]).map_err(de::Error::custom)?;
+		*offset += len;
+		Ok(value)
+	}
+}
+
+impl<'de, T> Visitor<'de> for DeltaVisitor<T>
+where
+	T: DeltaInteger,
+{
+	type Value = Vec<T>;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a delta encoded sequence of integers")
+	}
+
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		let mut offset: usize = 0;
+		let count = Self::next_usize(v, &mut offset)?;
+		// Every element takes at least one byte, which bounds the allocation by the input size
+		let mut res: Vec<T> = Vec::with_capacity(count.min(v.len() - offset));
+		let mut current: u64 = 0;
+		for _ in 0..count {
+			let delta = Self::next_usize(v, &mut offset)? as u64;
+			current = current
+				.checked_add(delta)
+				.ok_or_else(|| de::Error::custom("delta encoded value overflowed"))?;
+			res.push(
+				T::try_from(current)
+					.map_err(|_| de::Error::custom("delta encoded value is out of range"))?,
+			);
+		}
+		if offset != v.len() {
+			return Err(de::Error::invalid_length(v.len(), &self));
+		}
+		Ok(res)
+	}
+
+	fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.visit_bytes(&v)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Index {
+		#[serde(with = "crate::delta")]
+		pub wide: Vec<u64>,
+		#[serde(with = "crate::delta")]
+		pub narrow: Vec<u32>,
+	}
+
+	#[test]
+	fn test_small() {
+		let value = Index {
+			wide: vec![10, 20, 30],
+			narrow: vec![10, 20, 30],
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Struct length, then blob length, count and three single byte deltas per field
+		assert_eq!(
+			serialized,
+			vec![
+				0x02, 0x04, 0x03, 0x0A, 0x0A, 0x0A, 0x04, 0x03, 0x0A, 0x0A, 0x0A
+			]
+		);
+		let deserialized: Index = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_large() {
+		let value = Index {
+			wide: (0..1000).map(|i| 0x1_0000_0000 + i * 3).collect(),
+			narrow: (0..1000).collect(),
+		};
+		let serialized = Serializer::to_bytes(&value, true).unwrap();
+		assert!(serialized.len() < 2100);
+		let deserialized: Index = Deserializer::from_bytes(&serialized, true).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_unsorted() {
+		let value = Index {
+			wide: vec![10, 30, 20],
+			narrow: vec![],
+		};
+		assert!(Serializer::to_bytes(&value, false).is_err());
+	}
+
+	#[test]
+	fn test_truncated() {
+		let value = Index {
+			wide: vec![10, 20, 30],
+			narrow: vec![10, 20, 30],
+		};
+		let mut serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Claim one more element than the blob contains
+		serialized[2] = 0x04;
+		assert!(Deserializer::from_bytes::<Index>(&serialized, false).is_err());
+	}
+}
@@ -0,0 +1,154 @@
+//! Serialize enums as their explicit discriminant values rather than their variant indexes.
+//!
+//! Serde identifies a variant by its position in the enum, so `enum Code { A = 5, B = 100 }` is
+//! written with the indexes `0` and `1`. For protocols that define the value on the wire, use
+//! `#[serde(with = "serde_binary_adv::discriminant")]` on a field whose type implements
+//! `Discriminant`, and the discriminant is written as a number of the type given instead. Reading
+//! a value that is not the discriminant of any variant is an error.
+//!
+//! The discriminants are only available through an `as` cast, which needs the variant to be named,
+//! so `impl_discriminant!` implements `Discriminant` from the number type and a list of the
+//! variants. It supports enums with only unit variants:
+//!
+//! ```
+//! enum Code {
+//!     A = 5,
+//!     B = 100,
+//! }
+//!
+//! serde_binary_adv::impl_discriminant!(Code as u8 { A, B });
+//! ```
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+/// An enum whose variants are written as their discriminant values
+pub trait Discriminant: Sized {
+	/// The type the discriminant is written as
+	type Repr: Serialize + for<'de> Deserialize<'de> + Display;
+
+	/// Returns the discriminant of the variant
+	fn discriminant(&self) -> Self::Repr;
+
+	/// Returns the variant with the given discriminant, or `None` if there is none
+	fn from_discriminant(value: Self::Repr) -> Option<Self>;
+}
+
+/// Implements `Discriminant` for an enum with only unit variants, written as the given integer
+/// type.
+///
+/// Every variant must be listed, as `impl_discriminant!(Code as u8 { A, B })`.
+#[macro_export]
+macro_rules! impl_discriminant {
+	($ty:ty as $repr:ty { $($variant:ident),* $(,)? }) => {
+		impl $crate::discriminant::Discriminant for $ty {
+			type Repr = $repr;
+
+			fn discriminant(&self) -> $repr {
+				match self {
+					$(Self::$variant => Self::$variant as $repr,)*
+				}
+			}
+
+			fn from_discriminant(value: $repr) -> Option<Self> {
+				$(
+					if value == Self::$variant as $repr {
+						return Some(Self::$variant);
+					}
+				)*
+				None
+			}
+		}
+	};
+}
+
+/// Serializes a variant as its discriminant
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: Discriminant,
+{
+	value.discriminant().serialize(serializer)
+}
+
+/// Deserializes a variant from its discriminant
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: Discriminant,
+{
+	let value = T::Repr::deserialize(deserializer)?;
+	let message = format!("no variant has the discriminant {}", value);
+	T::from_discriminant(value).ok_or_else(|| de::Error::custom(message))
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Config, Deserializer, Serializer};
+
+	#[derive(Debug, PartialEq, Clone, Copy)]
+	enum Code {
+		A = 5,
+		B = 100,
+	}
+
+	impl_discriminant!(Code as u8 { A, B });
+
+	#[derive(Debug, PartialEq, Clone, Copy)]
+	#[repr(u16)]
+	enum Status {
+		Ok = 200,
+		NotFound = 404,
+	}
+
+	impl_discriminant!(Status as u16 { Ok, NotFound });
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Response {
+		#[serde(with = "crate::discriminant")]
+		pub code: Code,
+		#[serde(with = "crate::discriminant")]
+		pub status: Status,
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let value = Response {
+			code: Code::A,
+			status: Status::NotFound,
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		// Struct length, then each discriminant rather than its variant index
+		assert_eq!(serialized, vec![0x02, 0x05, 0x94, 0x01]);
+		let deserialized: Response = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
+
+		let value = Response {
+			code: Code::B,
+			status: Status::Ok,
+		};
+		let serialized = Serializer::to_bytes(&value, true).unwrap();
+		assert_eq!(serialized, vec![0x02, 0x64, 0x00, 0xC8]);
+		let deserialized: Response = Deserializer::from_bytes(&serialized, true).unwrap();
+		assert_eq!(value, deserialized);
+
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Response =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_unknown() {
+		// The variant index of `Code::B` is not a discriminant
+		assert!(Deserializer::from_bytes::<Response>(&[0x02, 0x01, 0xC8, 0x00], false).is_err());
+		assert!(Deserializer::from_bytes::<Response>(&[0x02, 0x05, 0xC9, 0x00], false).is_err());
+	}
+}
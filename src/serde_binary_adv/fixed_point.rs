@@ -0,0 +1,152 @@
+//! Serialize floating point numbers as fixed point integers.
+//!
+//! Some embedded protocols store real numbers as integers scaled by a power of two. Use with
+//! `#[serde(with = "serde_binary_adv::fixed_point::q16_16")]` on an `f64` field to write it as an
+//! `i32` with 16 integer bits and 16 fractional bits, in the byte order of the Serializer. The
+//! submodules are named for the Q format they write:
+//!
+//! - `q8_8`: an `i16`, with a resolution of 1/256 and a range of -128 to just under 128
+//! - `q16_16`: an `i32`, with a resolution of 1/65536 and a range of -32768 to just under 32768
+//! - `q32_32`: an `i64`, with a resolution of 2^-32 and a range of -2^31 to just under 2^31
+//!
+//! Values are rounded to the nearest multiple of the resolution, so a value read back may differ
+//! from the value written by up to half the resolution. Serializing a value outside the range, or
+//! one that is not a number, is an error.
+
+macro_rules! impl_fixed_point {
+	($name:ident, $int:ty, $serialize:ident, $frac_bits:expr, $doc:expr) => {
+		#[doc = $doc]
+		pub mod $name {
+			use serde::{Deserialize, Deserializer, Serializer, ser};
+
+			const SCALE: f64 = (1_u64 << $frac_bits) as f64;
+
+			/// Serializes a number as a scaled integer
+			pub fn serialize<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+			where
+				S: Serializer,
+			{
+				let scaled = (value * SCALE).round();
+				// The upper bound is exclusive as the maximum is one less than a power of two
+				if !(scaled >= <$int>::MIN as f64 && scaled < -(<$int>::MIN as f64)) {
+					return Err(ser::Error::custom(format!(
+						"{} cannot be represented in {}",
+						value,
+						stringify!($name)
+					)));
+				}
+				serializer.$serialize(scaled as $int)
+			}
+
+			/// Deserializes a number from a scaled integer
+			pub fn deserialize<'de, D>(deserializer: D) -> Result<f64, D::Error>
+			where
+				D: Deserializer<'de>,
+			{
+				Ok(<$int>::deserialize(deserializer)? as f64 / SCALE)
+			}
+		}
+	};
+}
+
+impl_fixed_point!(
+	q8_8,
+	i16,
+	serialize_i16,
+	8,
+	"Writes an `f64` as an `i16` with 8 fractional bits"
+);
+impl_fixed_point!(
+	q16_16,
+	i32,
+	serialize_i32,
+	16,
+	"Writes an `f64` as an `i32` with 16 fractional bits"
+);
+impl_fixed_point!(
+	q32_32,
+	i64,
+	serialize_i64,
+	32,
+	"Writes an `f64` as an `i64` with 32 fractional bits"
+);
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Reading {
+		#[serde(with = "crate::fixed_point::q16_16")]
+		pub value: f64,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Precise {
+		#[serde(with = "crate::fixed_point::q8_8")]
+		pub short: f64,
+		#[serde(with = "crate::fixed_point::q32_32")]
+		pub long: f64,
+	}
+
+	fn round_trip(value: f64, big_endian: bool) -> f64 {
+		let serialized = Serializer::to_bytes(&Reading { value }, big_endian).unwrap();
+		Deserializer::from_bytes::<Reading>(&serialized, big_endian)
+			.unwrap()
+			.value
+	}
+
+	#[test]
+	fn test_exact() {
+		let serialized = Serializer::to_bytes(&Reading { value: 1.5 }, false).unwrap();
+		// Struct length, then 0x0001_8000 in Little Endian order
+		assert_eq!(serialized, vec![0x01, 0x00, 0x80, 0x01, 0x00]);
+		let serialized = Serializer::to_bytes(&Reading { value: 1.5 }, true).unwrap();
+		assert_eq!(serialized, vec![0x01, 0x00, 0x01, 0x80, 0x00]);
+		let serialized = Serializer::to_bytes(&Reading { value: -0.25 }, false).unwrap();
+		assert_eq!(serialized, vec![0x01, 0x00, 0xC0, 0xFF, 0xFF]);
+		for big_endian in [false, true] {
+			assert_eq!(round_trip(1.5, big_endian), 1.5);
+			assert_eq!(round_trip(-0.25, big_endian), -0.25);
+		}
+	}
+
+	#[test]
+	fn test_precision() {
+		let resolution = 1.0 / 65536.0;
+		for value in [0.1, -1.23456, 32767.99998, -32768.0] {
+			for big_endian in [false, true] {
+				let error = (round_trip(value, big_endian) - value).abs();
+				assert!(error <= resolution / 2.0, "{} differs by {}", value, error);
+			}
+		}
+
+		let value = Precise {
+			short: 127.99,
+			long: -2147483647.123,
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(serialized.len(), 1 + 2 + 8);
+		let deserialized: Precise = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert!((deserialized.short - value.short).abs() <= 1.0 / 512.0);
+		assert!((deserialized.long - value.long).abs() <= 1.0 / 8589934592.0);
+	}
+
+	#[test]
+	fn test_out_of_range() {
+		assert!(Serializer::to_bytes(&Reading { value: 32768.0 }, false).is_err());
+		assert!(Serializer::to_bytes(&Reading { value: -32768.5 }, false).is_err());
+		assert!(Serializer::to_bytes(&Reading { value: f64::NAN }, false).is_err());
+		assert!(
+			Serializer::to_bytes(
+				&Reading {
+					value: f64::INFINITY
+				},
+				false
+			)
+			.is_err()
+		);
+	}
+}
@@ -0,0 +1,163 @@
+//! Serialize strings as a fixed number of bytes, for records with a fixed layout.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::fixed_str::FixedStr::<16>")]` on a `String` field
+//! to write exactly 16 bytes of UTF-8. A shorter string is padded with `0x00`, and a longer one is
+//! cut off at the last whole character that fits, so the data read back can be shorter than the
+//! string written. Trailing `0x00` bytes are removed when reading.
+//!
+//! The bytes are written as an array of `N` bytes, so they are only written without a length
+//! together with `Config::omit_tuple_lengths`. With the default `Config` the array is preceded by
+//! its length, which is the same for every value, and in self-describing mode each byte also has
+//! a type tag.
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+	Deserializer, Serializer,
+	de::{self, SeqAccess, Visitor},
+	ser::SerializeTuple,
+};
+
+/// Writes a string as exactly `N` bytes
+pub struct FixedStr<const N: usize>;
+
+impl<const N: usize> FixedStr<N> {
+	/// Serializes a string as `N` bytes, padded with `0x00` or cut off to fit
+	pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+		T: AsRef<str>,
+	{
+		let value = value.as_ref();
+		let len = (0..=N.min(value.len()))
+			.rev()
+			.find(|i| value.is_char_boundary(*i))
+			.unwrap_or(0);
+		let mut tuple = serializer.serialize_tuple(N)?;
+		for byte in value.as_bytes()[..len].iter() {
+			tuple.serialize_element(byte)?;
+		}
+		for _ in len..N {
+			tuple.serialize_element(&0x00_u8)?;
+		}
+		tuple.end()
+	}
+
+	/// Deserializes a string from `N` bytes, leaving out the trailing `0x00` bytes
+	pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+	where
+		D: Deserializer<'de>,
+		T: From<String>,
+	{
+		let s = deserializer.deserialize_tuple(N, FixedStrVisitor::<N>(PhantomData))?;
+		Ok(T::from(s))
+	}
+}
+
+struct FixedStrVisitor<const N: usize>(PhantomData<[u8; N]>);
+
+impl<'de, const N: usize> Visitor<'de> for FixedStrVisitor<N> {
+	type Value = String;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		write!(formatter, "a string of {} bytes", N)
+	}
+
+	fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+	where
+		A: SeqAccess<'de>,
+	{
+		let mut bytes: Vec<u8> = Vec::with_capacity(N);
+		for i in 0..N {
+			match seq.next_element::<u8>()? {
+				Some(byte) => bytes.push(byte),
+				None => return Err(de::Error::invalid_length(i, &self)),
+			}
+		}
+		let len = bytes.iter().rposition(|b| *b != 0x00).map_or(0, |i| i + 1);
+		bytes.truncate(len);
+		String::from_utf8(bytes).map_err(de::Error::custom)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Config, Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Record {
+		pub id: u8,
+		#[serde(with = "crate::fixed_str::FixedStr::<8>")]
+		pub name: String,
+		pub flags: u8,
+	}
+
+	fn config() -> Config {
+		Config {
+			omit_tuple_lengths: true,
+			..Config::default()
+		}
+	}
+
+	fn record(name: &str) -> Record {
+		Record {
+			id: 0x41,
+			name: String::from(name),
+			flags: 0x42,
+		}
+	}
+
+	// Returns the bytes written for a record with the given name, and the record read back
+	fn round_trip(name: &str, config: &Config) -> (Vec<u8>, Record) {
+		let serialized = Serializer::to_bytes_with_config(&record(name), config).unwrap();
+		let deserialized = Deserializer::from_bytes_with_config(&serialized, config).unwrap();
+		(serialized, deserialized)
+	}
+
+	#[test]
+	fn test_padded() {
+		let (serialized, deserialized) = round_trip("ABC", &config());
+		// Struct length, id, the padded name and flags
+		assert_eq!(
+			serialized,
+			vec![
+				0x03, 0x41, b'A', b'B', b'C', 0x00, 0x00, 0x00, 0x00, 0x00, 0x42
+			]
+		);
+		assert_eq!(deserialized, record("ABC"));
+
+		// Every record is the same size
+		let (serialized, deserialized) = round_trip("", &config());
+		assert_eq!(serialized.len(), 11);
+		assert_eq!(deserialized, record(""));
+	}
+
+	#[test]
+	fn test_default_config() {
+		// Without `omit_tuple_lengths` the padded name is preceded by the array length
+		let (serialized, deserialized) = round_trip("ABC", &Config::default());
+		assert_eq!(
+			serialized,
+			vec![
+				0x03, 0x41, 0x08, b'A', b'B', b'C', 0x00, 0x00, 0x00, 0x00, 0x00, 0x42
+			]
+		);
+		assert_eq!(serialized.len(), 8 + 4);
+		assert_eq!(deserialized, record("ABC"));
+	}
+
+	#[test]
+	fn test_truncated() {
+		let (serialized, deserialized) = round_trip("ABCDEFGHIJ", &config());
+		assert_eq!(serialized.len(), 11);
+		assert_eq!(&serialized[2..10], b"ABCDEFGH");
+		assert_eq!(deserialized, record("ABCDEFGH"));
+
+		// A character that does not fit is left out whole
+		let (serialized, deserialized) = round_trip("ABCDEFG\u{E9}", &config());
+		assert_eq!(&serialized[2..10], b"ABCDEFG\0");
+		assert_eq!(deserialized, record("ABCDEFG"));
+	}
+}
@@ -0,0 +1,133 @@
+//! Decode values from bytes that arrive a piece at a time.
+
+use serde::de::DeserializeOwned;
+
+use super::{Config, Deserializer, Result};
+
+/// Buffers bytes pushed to it, such as by a non-blocking framework without `AsyncRead`, and
+/// decodes each value once all of its bytes have arrived.
+///
+/// Each call to `try_take` decodes from the start of the bytes buffered, so a value that arrives
+/// in many small pieces is decoded many times over. Values are read one after another, as written
+/// by calling the Serializer once for each.
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalDeserializer {
+	buffer: Vec<u8>,
+	config: Config,
+}
+
+impl IncrementalDeserializer {
+	/// Creates an incremental deserializer
+	pub fn new(big_endian: bool) -> Self {
+		Self::with_config(Config::new(big_endian))
+	}
+
+	/// Creates an incremental deserializer using the given settings
+	pub fn with_config(config: Config) -> Self {
+		Self {
+			buffer: Vec::new(),
+			config,
+		}
+	}
+
+	/// Adds bytes to the end of the buffer
+	pub fn feed(&mut self, data: &[u8]) {
+		self.buffer.extend_from_slice(data);
+	}
+
+	/// Decodes the next value and removes its bytes from the buffer, or returns `None` if the
+	/// buffer ends before the value does.
+	///
+	/// Data that is invalid is an error, and stays in the buffer.
+	pub fn try_take<T>(&mut self) -> Result<Option<T>>
+	where
+		T: DeserializeOwned,
+	{
+		match Deserializer::from_prefix(&self.buffer, &self.config)? {
+			Some((value, len)) => {
+				self.buffer.drain(..len);
+				Ok(Some(value))
+			}
+			None => Ok(None),
+		}
+	}
+
+	/// Returns the number of bytes buffered but not yet decoded
+	pub fn buffered(&self) -> usize {
+		self.buffer.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use super::IncrementalDeserializer;
+	use crate::{Config, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Reading {
+		pub id: u64,
+		pub label: String,
+		pub values: Vec<f32>,
+		pub flag: Option<bool>,
+	}
+
+	fn reading() -> Reading {
+		Reading {
+			id: 0x41,
+			label: String::from("test"),
+			values: vec![1.5, -2.5],
+			flag: Some(true),
+		}
+	}
+
+	#[test]
+	fn test_one_byte_at_a_time() {
+		let configs = [
+			Config::default(),
+			Config::new(true),
+			Config {
+				self_describing: true,
+				fingerprint: true,
+				..Config::default()
+			},
+		];
+		for config in configs {
+			let serialized = Serializer::to_bytes_with_config(&reading(), &config).unwrap();
+			let mut deserializer = IncrementalDeserializer::with_config(config);
+			for (i, byte) in serialized.iter().enumerate() {
+				deserializer.feed(&[*byte]);
+				let taken = deserializer.try_take::<Reading>().unwrap();
+				if i + 1 < serialized.len() {
+					assert_eq!(taken, None);
+				} else {
+					assert_eq!(taken, Some(reading()));
+				}
+			}
+			assert_eq!(deserializer.buffered(), 0);
+			assert_eq!(deserializer.try_take::<Reading>().unwrap(), None);
+		}
+	}
+
+	#[test]
+	fn test_several_values() {
+		let mut serialized = Serializer::to_bytes(&reading(), false).unwrap();
+		serialized.extend(Serializer::to_bytes(&0x42_u16, false).unwrap());
+		let mut deserializer = IncrementalDeserializer::new(false);
+		deserializer.feed(&serialized[..serialized.len() - 1]);
+		assert_eq!(deserializer.try_take::<Reading>().unwrap(), Some(reading()));
+		assert_eq!(deserializer.try_take::<u16>().unwrap(), None);
+		deserializer.feed(&serialized[serialized.len() - 1..]);
+		assert_eq!(deserializer.try_take::<u16>().unwrap(), Some(0x42));
+	}
+
+	#[test]
+	fn test_invalid() {
+		let mut deserializer = IncrementalDeserializer::new(false);
+		// A `char` that is not UTF-8
+		deserializer.feed(&[0xC0, 0x41]);
+		assert!(deserializer.try_take::<char>().is_err());
+		assert_eq!(deserializer.buffered(), 2);
+	}
+}
@@ -0,0 +1,103 @@
+//! Serialize collections that are always empty as nothing at all.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::omit_empty")]` on a field whose value is always
+//! empty, such as a `Vec` or `String` reserved for a later version of a fixed schema. Collections
+//! are normally written with their length, so an empty one takes one byte; this writes nothing,
+//! and the Deserializer produces an empty collection without reading anything.
+//!
+//! Any type with a `Default` value that it can be compared to is supported. Serializing a value
+//! that is not empty is an error, since it could not be read back. Data written with this module
+//! can only be read with it, as nothing in the data marks the field as absent. In self-describing
+//! mode the field is written as a unit, so the data can still be read without knowing its type.
+
+use serde::{Deserialize, Deserializer, Serializer, ser};
+
+/// Serializes an empty collection as nothing
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: Default + PartialEq,
+{
+	if *value != T::default() {
+		return Err(ser::Error::custom(
+			"omit_empty requires a value that is always empty",
+		));
+	}
+	serializer.serialize_unit()
+}
+
+/// Deserializes an empty collection without reading anything
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: Default,
+{
+	<()>::deserialize(deserializer)?;
+	Ok(T::default())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Config, Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Record {
+		pub id: u8,
+		#[serde(with = "crate::omit_empty")]
+		pub reserved: Vec<u32>,
+		#[serde(with = "crate::omit_empty")]
+		pub comment: String,
+		#[serde(with = "crate::omit_empty")]
+		pub extra: HashMap<String, u8>,
+		pub flag: bool,
+	}
+
+	fn empty_record() -> Record {
+		Record {
+			id: 0x41,
+			reserved: Vec::new(),
+			comment: String::new(),
+			extra: HashMap::new(),
+			flag: true,
+		}
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let value = empty_record();
+		for big_endian in [false, true] {
+			let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+			// Struct length and the two other fields, with nothing for the empty ones
+			assert_eq!(serialized, vec![0x05, 0x41, 0x01]);
+			let deserialized: Record = Deserializer::from_bytes(&serialized, big_endian).unwrap();
+			assert_eq!(value, deserialized);
+		}
+
+		let config = Config {
+			self_describing: true,
+			..Config::default()
+		};
+		let serialized = Serializer::to_bytes_with_config(&value, &config).unwrap();
+		let deserialized: Record =
+			Deserializer::from_bytes_with_config(&serialized, &config).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_not_empty() {
+		let value = Record {
+			reserved: vec![0x01],
+			..empty_record()
+		};
+		assert!(Serializer::to_bytes(&value, false).is_err());
+		let value = Record {
+			comment: String::from("test"),
+			..empty_record()
+		};
+		assert!(Serializer::to_bytes(&value, false).is_err());
+	}
+}
@@ -0,0 +1,140 @@
+//! Serialize operating system strings and paths without requiring them to be UTF-8.
+//!
+//! Serde serializes `OsString` and `PathBuf` values as strings, so a path that is not valid UTF-8
+//! fails to serialize. Use with `#[serde(with = "serde_binary_adv::os_str")]` on an `OsString` or
+//! `PathBuf` field to store the raw platform representation instead: the bytes on Unix, and the
+//! UTF-16 code units on Windows.
+//!
+//! The representation is platform specific, so data written on Unix can only be read on Unix and
+//! data written on Windows can only be read on Windows.
+
+use std::ffi::{OsStr, OsString};
+
+use serde::{Deserializer, Serializer};
+
+/// Serializes the raw bytes of an operating system string
+#[cfg(unix)]
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: AsRef<OsStr>,
+{
+	use std::os::unix::ffi::OsStrExt;
+
+	serializer.serialize_bytes(value.as_ref().as_bytes())
+}
+
+/// Deserializes the raw bytes of an operating system string
+#[cfg(unix)]
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: From<OsString>,
+{
+	use std::os::unix::ffi::OsStringExt;
+
+	let bytes = deserializer.deserialize_byte_buf(BytesVisitor)?;
+	Ok(T::from(OsString::from_vec(bytes)))
+}
+
+/// Serializes the UTF-16 code units of an operating system string
+#[cfg(windows)]
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: AsRef<OsStr>,
+{
+	use serde::Serialize;
+	use std::os::windows::ffi::OsStrExt;
+
+	let wide: Vec<u16> = value.as_ref().encode_wide().collect();
+	wide.serialize(serializer)
+}
+
+/// Deserializes the UTF-16 code units of an operating system string
+#[cfg(windows)]
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: From<OsString>,
+{
+	use serde::Deserialize;
+	use std::os::windows::ffi::OsStringExt;
+
+	let wide = Vec::<u16>::deserialize(deserializer)?;
+	Ok(T::from(OsString::from_wide(&wide)))
+}
+
+#[cfg(unix)]
+struct BytesVisitor;
+
+#[cfg(unix)]
+impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+	type Value = Vec<u8>;
+
+	fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+		formatter.write_str("the bytes of an operating system string")
+	}
+
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+	where
+		E: serde::de::Error,
+	{
+		Ok(v.to_vec())
+	}
+
+	fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+	where
+		E: serde::de::Error,
+	{
+		Ok(v)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{ffi::OsString, path::PathBuf};
+
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Paths {
+		#[serde(with = "crate::os_str")]
+		pub path: PathBuf,
+		#[serde(with = "crate::os_str")]
+		pub name: OsString,
+	}
+
+	#[test]
+	fn test_path() {
+		let value = Paths {
+			path: PathBuf::from("/usr/local/bin"),
+			name: OsString::from("test"),
+		};
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		let deserialized: Paths = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn test_non_utf8_path() {
+		use std::os::unix::ffi::OsStringExt;
+
+		let value = Paths {
+			path: PathBuf::from(OsString::from_vec(vec![b'/', 0xFF, 0xFE, b'a'])),
+			name: OsString::from_vec(vec![0x80]),
+		};
+		// Serde's own implementation rejects paths that are not UTF-8
+		assert!(Serializer::to_bytes(&value.path, false).is_err());
+		let serialized = Serializer::to_bytes(&value, false).unwrap();
+		assert_eq!(
+			serialized,
+			vec![0x02, 0x04, b'/', 0xFF, 0xFE, b'a', 0x01, 0x80]
+		);
+		let deserialized: Paths = Deserializer::from_bytes(&serialized, false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+}
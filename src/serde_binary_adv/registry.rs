@@ -0,0 +1,151 @@
+//! Read values whose type is only known from an id written before them.
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+
+use super::{BinaryError, Deserializer, Result};
+
+type DeserializeFn<T> =
+	Box<dyn for<'a, 'de> Fn(&'a mut Deserializer<'de>) -> Result<T> + Send + Sync>;
+
+/// Maps `u16` type ids to the types they stand for, so that records of different types can be
+/// stored together and read back as a common type, such as a trait object.
+///
+/// Write each value with `Serializer::to_bytes_typed`, and read it with
+/// `Deserializer::from_bytes_typed`, which reads the id and deserializes the type registered for
+/// it.
+pub struct TypeRegistry<T> {
+	types: HashMap<u16, DeserializeFn<T>>,
+}
+
+impl<T> TypeRegistry<T> {
+	/// Creates an empty registry
+	pub fn new() -> Self {
+		Self {
+			types: HashMap::new(),
+		}
+	}
+
+	/// Registers the type `V` under `id`, with the conversion into the common type, replacing any
+	/// type already registered under it
+	pub fn register<V, F>(&mut self, id: u16, convert: F)
+	where
+		V: DeserializeOwned,
+		F: Fn(V) -> T + Send + Sync + 'static,
+	{
+		self.types.insert(
+			id,
+			Box::new(move |deserializer| V::deserialize(deserializer).map(&convert)),
+		);
+	}
+
+	/// Returns true if a type is registered under `id`
+	pub fn contains(&self, id: u16) -> bool {
+		self.types.contains_key(&id)
+	}
+
+	pub(crate) fn deserialize(&self, id: u16, deserializer: &mut Deserializer) -> Result<T> {
+		match self.types.get(&id) {
+			Some(f) => f(deserializer),
+			None => Err(BinaryError::Message {
+				message: format!("no type registered for type id {}", id),
+			}),
+		}
+	}
+}
+
+impl<T> Default for TypeRegistry<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use super::TypeRegistry;
+	use crate::{BinaryError, Deserializer, Serializer};
+
+	trait Shape {
+		fn area(&self) -> f64;
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Square {
+		pub side: f64,
+	}
+
+	impl Shape for Square {
+		fn area(&self) -> f64 {
+			self.side * self.side
+		}
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Rectangle {
+		pub width: f64,
+		pub height: f64,
+	}
+
+	impl Shape for Rectangle {
+		fn area(&self) -> f64 {
+			self.width * self.height
+		}
+	}
+
+	const SQUARE: u16 = 1;
+	const RECTANGLE: u16 = 0x0102;
+
+	fn registry() -> TypeRegistry<Box<dyn Shape>> {
+		let mut registry: TypeRegistry<Box<dyn Shape>> = TypeRegistry::new();
+		registry.register(SQUARE, |v: Square| Box::new(v) as Box<dyn Shape>);
+		registry.register(RECTANGLE, |v: Rectangle| Box::new(v) as Box<dyn Shape>);
+		registry
+	}
+
+	#[test]
+	fn test_typed() {
+		let registry = registry();
+		assert!(registry.contains(SQUARE));
+		assert!(!registry.contains(3));
+
+		for big_endian in [false, true] {
+			let records = [
+				Serializer::to_bytes_typed(SQUARE, &Square { side: 2.0 }, big_endian).unwrap(),
+				Serializer::to_bytes_typed(
+					RECTANGLE,
+					&Rectangle {
+						width: 2.0,
+						height: 3.0,
+					},
+					big_endian,
+				)
+				.unwrap(),
+			];
+			let shapes: Vec<Box<dyn Shape>> = records
+				.iter()
+				.map(|record| Deserializer::from_bytes_typed(&registry, record, big_endian))
+				.collect::<Result<_, _>>()
+				.unwrap();
+			assert_eq!(shapes.len(), 2);
+			assert_eq!(shapes[0].area(), 4.0);
+			assert_eq!(shapes[1].area(), 6.0);
+		}
+
+		// The id is written before the value, in the byte order of numbers
+		let serialized = Serializer::to_bytes_typed(RECTANGLE, &(), true).unwrap();
+		assert_eq!(serialized, vec![0x01, 0x02]);
+	}
+
+	#[test]
+	fn test_unknown_type() {
+		let serialized = Serializer::to_bytes_typed(3, &Square { side: 2.0 }, false).unwrap();
+		assert!(matches!(
+			Deserializer::from_bytes_typed(&registry(), &serialized, false),
+			Err(BinaryError::Message { .. })
+		));
+		assert!(Deserializer::from_bytes_typed(&registry(), &[0x01], false).is_err());
+	}
+}
@@ -0,0 +1,217 @@
+//! Describe the fields a struct is expected to have, to find where self-describing data differs.
+
+use std::fmt::Display;
+
+use super::common::{
+	flags::{NONE, NONUNIT_VARIANT, SOME, STRUCT_VARIANT, UNIT_VARIANT},
+	tags,
+};
+
+/// The type of a value written in self-describing mode, as given by its tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+	/// `()` and unit structs
+	Unit,
+	/// `bool`
+	Bool,
+	/// `u8`
+	U8,
+	/// `u16`
+	U16,
+	/// `u32`
+	U32,
+	/// `u64`
+	U64,
+	/// `u128`
+	U128,
+	/// `i8`
+	I8,
+	/// `i16`
+	I16,
+	/// `i32`
+	I32,
+	/// `i64`
+	I64,
+	/// `i128`
+	I128,
+	/// `f32`
+	F32,
+	/// `f64`
+	F64,
+	/// `char`
+	Char,
+	/// Strings
+	Str,
+	/// Byte arrays, such as `serde_bytes::ByteBuf`
+	Bytes,
+	/// Sequences, tuples and tuple structs
+	Seq,
+	/// Maps and structs, which are written keyed by field name
+	Map,
+	/// Newtype structs, when written with their names
+	Newtype,
+	/// `Option`, whether `None` or `Some`
+	Option,
+	/// Enum variants
+	Variant,
+}
+
+impl ValueType {
+	// Returns the type a tag or flag stands for
+	pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+		Some(match tag {
+			tags::UNIT => Self::Unit,
+			tags::BOOL => Self::Bool,
+			tags::U8 => Self::U8,
+			tags::U16 => Self::U16,
+			tags::U32 => Self::U32,
+			tags::U64 => Self::U64,
+			tags::U128 => Self::U128,
+			tags::I8 => Self::I8,
+			tags::I16 => Self::I16,
+			tags::I32 => Self::I32,
+			tags::I64 => Self::I64,
+			tags::I128 => Self::I128,
+			tags::F32 => Self::F32,
+			tags::F64 => Self::F64,
+			tags::CHAR => Self::Char,
+			tags::STR => Self::Str,
+			tags::BYTES => Self::Bytes,
+			tags::SEQ => Self::Seq,
+			tags::MAP => Self::Map,
+			tags::NEWTYPE => Self::Newtype,
+			NONE | SOME => Self::Option,
+			UNIT_VARIANT | NONUNIT_VARIANT | STRUCT_VARIANT => Self::Variant,
+			_ => return None,
+		})
+	}
+}
+
+impl Display for ValueType {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = match self {
+			Self::Unit => "unit",
+			Self::Bool => "bool",
+			Self::U8 => "u8",
+			Self::U16 => "u16",
+			Self::U32 => "u32",
+			Self::U64 => "u64",
+			Self::U128 => "u128",
+			Self::I8 => "i8",
+			Self::I16 => "i16",
+			Self::I32 => "i32",
+			Self::I64 => "i64",
+			Self::I128 => "i128",
+			Self::F32 => "f32",
+			Self::F64 => "f64",
+			Self::Char => "char",
+			Self::Str => "string",
+			Self::Bytes => "bytes",
+			Self::Seq => "sequence",
+			Self::Map => "map",
+			Self::Newtype => "newtype struct",
+			Self::Option => "option",
+			Self::Variant => "enum variant",
+		};
+		f.write_str(name)
+	}
+}
+
+/// The fields a struct is expected to have, and the type of each, for
+/// `Deserializer::from_bytes_validated`.
+///
+/// Only the type of each field is described, not the types inside it, so a field that is a
+/// struct is only checked to be a map.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+	fields: Vec<(String, ValueType)>,
+}
+
+impl Schema {
+	/// Creates a schema without any fields
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a field of the given type, replacing any field already added with the same name
+	pub fn add_field(&mut self, name: &str, value_type: ValueType) {
+		match self.fields.iter_mut().find(|(n, _)| n == name) {
+			Some(field) => field.1 = value_type,
+			None => self.fields.push((String::from(name), value_type)),
+		}
+	}
+
+	pub(crate) fn fields(&self) -> &[(String, ValueType)] {
+		&self.fields
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use super::{Schema, ValueType};
+	use crate::{BinaryError, Config, Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Reading {
+		pub id: u64,
+		pub label: String,
+		pub value: Option<f32>,
+	}
+
+	fn config() -> Config {
+		Config {
+			self_describing: true,
+			..Config::default()
+		}
+	}
+
+	fn reading() -> Reading {
+		Reading {
+			id: 0x41,
+			label: String::from("test"),
+			value: None,
+		}
+	}
+
+	#[test]
+	fn test_validated() {
+		let serialized = Serializer::to_bytes_with_config(&reading(), &config()).unwrap();
+		let mut schema = Schema::new();
+		schema.add_field("id", ValueType::U64);
+		schema.add_field("value", ValueType::Option);
+		let deserialized: Reading =
+			Deserializer::from_bytes_validated(&serialized, &schema, &config()).unwrap();
+		assert_eq!(reading(), deserialized);
+
+		schema.add_field("id", ValueType::U32);
+		assert_eq!(
+			Deserializer::from_bytes_validated::<Reading>(&serialized, &schema, &config()),
+			Err(BinaryError::Message {
+				message: String::from("field `id` is u64, expected u32")
+			})
+		);
+	}
+
+	#[test]
+	fn test_missing_field() {
+		let serialized = Serializer::to_bytes_with_config(&reading(), &config()).unwrap();
+		let mut schema = Schema::new();
+		schema.add_field("label", ValueType::Str);
+		schema.add_field("count", ValueType::U32);
+		assert_eq!(
+			Deserializer::from_bytes_validated::<Reading>(&serialized, &schema, &config()),
+			Err(BinaryError::Message {
+				message: String::from("missing field `count`")
+			})
+		);
+
+		// Without type tags there is nothing to check against
+		let serialized = Serializer::to_bytes(&reading(), false).unwrap();
+		assert!(
+			Deserializer::from_bytes_validated::<Reading>(&serialized, &schema, &Config::default())
+				.is_err()
+		);
+	}
+}
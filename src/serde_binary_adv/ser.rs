@@ -1,62 +1,300 @@
 //! Serialize a Rust structure into binary data.
 
+use std::collections::HashMap;
+
 use crate::serde_binary_adv::common::{
-	compress_usize,
+	compress_usize, compress_usize_as, compress_usize_as_into, fingerprint,
 	flags::{self, NONUNIT_VARIANT, STRUCT_VARIANT, UNIT_VARIANT},
+	tags,
 };
 
 use super::BinaryError;
+use super::Config;
 use super::Result;
+use super::StringType;
 use num::traits::ToBytes;
 use serde::{Serialize, ser};
 
 /// A structure for serializing Rust values into binary.
+///
+/// The Serializer is `Send`, so it can be moved to another thread.
 pub struct Serializer {
 	output: Vec<u8>,
-	big_endian: bool,
+	config: Config,
+	// The presence bitmap of each struct being written, innermost last
+	bitmaps: Vec<Bitmap>,
+	field_present: bool,
+	// The name and field names of the top level struct, when writing a fingerprint
+	schema: Option<Vec<&'static str>>,
+	struct_depth: usize,
+	// The position and number of entries of each sequence or map being written, innermost last,
+	// or `None` for those whose length was given up front
+	collections: Vec<Option<Collection>>,
+	// The start of each entry of each map being written in canonical order, innermost last
+	map_entries: Vec<Vec<usize>>,
+	// The start of each map entry being written with its length, innermost last
+	map_frames: Vec<usize>,
+	// The position in the table of each string written so far, when interning strings
+	strings: HashMap<String, usize>,
+	// The length of each struct being written, innermost last
+	fields: Vec<Fields>,
+	// The position of the element size of each sequence being written whose first element is
+	// still to come, innermost last, when validating element sizes
+	element_sizes: Vec<Option<usize>>,
+	// The start and end of the last number written
+	last_number: (usize, usize),
+}
+
+struct Collection {
+	position: usize,
+	len: usize,
+}
+
+// The number of fields a struct was declared with, and how many of them have been written
+struct Fields {
+	position: usize,
+	len: usize,
+	written: usize,
+}
+
+struct Bitmap {
+	position: usize,
+	len: usize,
+	field: usize,
 }
 
 impl Serializer {
 	/// Converts a Rust value into a binary representation and returns a `Vec<u8>` of the bytes
 	pub fn to_bytes<T>(value: &T, big_endian: bool) -> Result<Vec<u8>>
 	where
-		T: Serialize,
+		T: ?Sized + Serialize,
+	{
+		Self::to_bytes_with_config(value, &Config::new(big_endian))
+	}
+
+	/// Converts a Rust value into a binary representation using the given settings and returns a
+	/// `Vec<u8>` of the bytes
+	pub fn to_bytes_with_config<T>(value: &T, config: &Config) -> Result<Vec<u8>>
+	where
+		T: ?Sized + Serialize,
+	{
+		let mut serializer = Self::with_config(config.clone());
+		value.serialize(&mut serializer)?;
+		if !config.fingerprint {
+			return Ok(serializer.output);
+		}
+		let schema = serializer.schema.take().unwrap_or_default();
+		let mut output = Self::with_config(config.clone());
+		output.serialize_num(fingerprint(schema))?;
+		output.output.append(&mut serializer.output);
+		Ok(output.output)
+	}
+
+	/// Converts a Rust value into a binary representation preceded by a `u16` type id, for reading
+	/// with `Deserializer::from_bytes_typed`
+	pub fn to_bytes_typed<T>(id: u16, value: &T, big_endian: bool) -> Result<Vec<u8>>
+	where
+		T: ?Sized + Serialize,
+	{
+		let mut serializer = Self::new(big_endian);
+		serializer.serialize_num(id)?;
+		value.serialize(&mut serializer)?;
+		Ok(serializer.output)
+	}
+
+	/// Converts a Rust value into a binary representation preceded by a `u32` magic number, for
+	/// reading with `Deserializer::from_bytes_detect_endian`
+	pub fn to_bytes_with_magic<T>(magic: u32, value: &T, big_endian: bool) -> Result<Vec<u8>>
+	where
+		T: ?Sized + Serialize,
 	{
 		let mut serializer = Self::new(big_endian);
+		serializer.serialize_num(magic)?;
 		value.serialize(&mut serializer)?;
 		Ok(serializer.output)
 	}
 
+	/// Converts a Rust value into a length prefixed frame, consisting of the compressed length of
+	/// the binary representation followed by the representation itself
+	pub fn to_frame<T>(value: &T, big_endian: bool) -> Result<Vec<u8>>
+	where
+		T: ?Sized + Serialize,
+	{
+		let payload = Self::to_bytes(value, big_endian)?;
+		let mut frame = compress_usize(payload.len());
+		frame.extend_from_slice(&payload);
+		Ok(frame)
+	}
+
 	/// Creates a new binary Serializer
 	pub fn new(big_endian: bool) -> Self {
+		Self::with_config(Config::new(big_endian))
+	}
+
+	/// Creates a new binary Serializer using the given settings
+	pub fn with_config(config: Config) -> Self {
 		Self {
 			output: Vec::new(),
-			big_endian,
+			config,
+			bitmaps: Vec::new(),
+			field_present: true,
+			schema: None,
+			struct_depth: 0,
+			collections: Vec::new(),
+			map_entries: Vec::new(),
+			map_frames: Vec::new(),
+			strings: HashMap::new(),
+			fields: Vec::new(),
+			element_sizes: Vec::new(),
+			last_number: (0, 0),
 		}
 	}
 
-	fn serialize_num<T: ToBytes>(self: &mut Self, v: T) -> Result<()> {
-		if self.big_endian {
-			self.output.append(&mut v.to_be_bytes().as_mut().to_vec());
+	fn serialize_num<T: ToBytes>(&mut self, v: T) -> Result<()> {
+		let start = self.output.len();
+		if self.config.big_endian {
+			self.output.extend_from_slice(v.to_be_bytes().as_ref());
 		} else {
-			self.output.append(&mut v.to_le_bytes().as_mut().to_vec());
+			self.output.extend_from_slice(v.to_le_bytes().as_ref());
 		}
+		self.last_number = (start, self.output.len());
 		Ok(())
 	}
 
-	fn serialize_vec<T: ToBytes>(self: &mut Self, v: Vec<T>) -> Result<()> {
-		for item in v {
-			self.serialize_num(item).unwrap()
+	// Writes a float as its bytes, or as decimal text when debugging floats
+	fn serialize_float<T: ToBytes + ryu::Float>(&mut self, v: T) -> Result<()> {
+		if !(self.config.debug_floats && self.config.self_describing) {
+			return self.serialize_num(v);
 		}
+		let mut buf = ryu::Buffer::new();
+		let text = buf.format(v);
+		self.serialize_usize(text.len())?;
+		self.serialize_slice(text.as_bytes())
+	}
+
+	fn serialize_slice(&mut self, v: &[u8]) -> Result<()> {
+		self.output.extend_from_slice(v);
 		Ok(())
 	}
 
 	fn serialize_usize(&mut self, v: usize) -> Result<()> {
-		self.serialize_vec(compress_usize(v))
+		compress_usize_as_into(v, self.config.varint_endianness, &mut self.output);
+		Ok(())
+	}
+
+	// Writes the type tag in self-describing mode
+	fn tag(&mut self, tag: u8) -> Result<()> {
+		if self.config.self_describing {
+			self.output.push(tag);
+		}
+		Ok(())
+	}
+
+	// Starts a sequence or map, writing its length if known or remembering where to insert it
+	// once all the entries have been written
+	fn start_collection(&mut self, len: Option<usize>) -> Result<()> {
+		match len {
+			Some(n) => {
+				self.serialize_usize(n)?;
+				self.collections.push(None);
+			}
+			None => self.collections.push(Some(Collection {
+				position: self.output.len(),
+				len: 0,
+			})),
+		}
+		Ok(())
+	}
+
+	fn count_entry(&mut self) {
+		if let Some(Some(collection)) = self.collections.last_mut() {
+			collection.len += 1;
+		}
+	}
+
+	fn end_collection(&mut self) -> Result<()> {
+		// The length is written after the entries and rotated into place before them, so that
+		// nested collections of unknown length need no buffer of their own
+		if let Some(Some(collection)) = self.collections.pop() {
+			let end = self.output.len();
+			self.serialize_usize(collection.len)?;
+			self.output[collection.position..].rotate_left(end - collection.position);
+		}
+		Ok(())
+	}
+
+	fn framed_map_entries(&self) -> bool {
+		self.config.framed_map_entries && !self.config.self_describing
+	}
+
+	// Starts a struct, writing the number of fields it was declared with
+	fn start_fields(&mut self, len: usize) -> Result<()> {
+		self.fields.push(Fields {
+			position: self.output.len(),
+			len,
+			written: 0,
+		});
+		self.serialize_usize(len)
+	}
+
+	fn count_field(&mut self) {
+		if let Some(fields) = self.fields.last_mut() {
+			fields.written += 1;
+		}
+	}
+
+	// Ends a struct, rewriting its length, and shrinking its presence bitmap, if fewer fields
+	// were written than it was declared with, such as when `skip_field` was called
+	fn end_fields(&mut self, bitmap: bool) {
+		let Some(fields) = self.fields.pop() else {
+			return;
+		};
+		if fields.written >= fields.len {
+			return;
+		}
+		let declared = compress_usize_as(fields.len, self.config.varint_endianness);
+		let len = fields.written;
+		let actual = compress_usize_as(len, self.config.varint_endianness);
+		let bitmap_start = fields.position + actual.len();
+		self.output
+			.splice(fields.position..fields.position + declared.len(), actual);
+		if bitmap {
+			// Skipped fields never set a bit, so the bytes removed are always empty
+			let start = bitmap_start + len.div_ceil(8);
+			self.output
+				.drain(start..bitmap_start + fields.len.div_ceil(8));
+		}
+	}
+
+	// Sorts the entries of a map, which run from the first start to the end of the output
+	fn sort_entries(&mut self, starts: Vec<usize>) {
+		let Some(&first) = starts.first() else {
+			return;
+		};
+		let ends = starts.iter().skip(1).copied().chain([self.output.len()]);
+		let mut entries: Vec<Vec<u8>> = starts
+			.iter()
+			.zip(ends)
+			.map(|(&start, end)| self.output[start..end].to_vec())
+			.collect();
+		entries.sort_unstable();
+		self.output.truncate(first);
+		self.output.extend(entries.concat());
+	}
+
+	// Writes the variant flag, followed by the variant index, or the variant name in
+	// self-describing mode
+	fn serialize_variant(&mut self, flag: u8, variant_index: u32, variant: &str) -> Result<()> {
+		self.output.push(flag);
+		if self.config.self_describing {
+			ser::Serializer::serialize_str(self, variant)
+		} else {
+			self.serialize_num(variant_index)
+		}
 	}
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -69,105 +307,155 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 	type SerializeStructVariant = Self;
 
 	fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
-		self.serialize_u8(if v { 1 } else { 0 })
+		self.tag(tags::BOOL)?;
+		self.serialize_num(if v { 1_u8 } else { 0_u8 })
 	}
 
 	fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+		self.tag(tags::U8)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+		self.tag(tags::U16)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+		self.tag(tags::U32)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+		self.tag(tags::U64)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+		self.tag(tags::U128)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+		self.tag(tags::I8)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+		self.tag(tags::I16)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+		self.tag(tags::I32)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+		self.tag(tags::I64)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+		self.tag(tags::I128)?;
 		self.serialize_num(v)
 	}
 
 	fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
-		self.serialize_num(v)
+		self.tag(tags::F32)?;
+		if self.config.canonical && v.is_nan() {
+			return self.serialize_float(f32::NAN);
+		}
+		self.serialize_float(v)
 	}
 
 	fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
-		self.serialize_num(v)
+		self.tag(tags::F64)?;
+		if self.config.canonical && v.is_nan() {
+			return self.serialize_float(f64::NAN);
+		}
+		self.serialize_float(v)
 	}
 
 	fn serialize_char(self, v: char) -> Result<Self::Ok> {
 		let mut buf: [u8; 4] = [0, 0, 0, 0];
-		self.serialize_vec(v.encode_utf8(&mut buf).as_bytes().to_vec())
+		self.tag(tags::CHAR)?;
+		self.serialize_slice(v.encode_utf8(&mut buf).as_bytes())
 	}
 
 	fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-		self.serialize_usize(v.bytes().len()).unwrap();
-		self.serialize_vec(v.as_bytes().to_vec())
+		self.tag(tags::STR)?;
+		if self.config.intern_strings {
+			if self.config.canonical {
+				return Err(BinaryError::Message {
+					message: String::from("intern_strings cannot be combined with canonical"),
+				});
+			}
+			if let Some(&index) = self.strings.get(v) {
+				return self.serialize_usize(index + 1);
+			}
+			self.strings.insert(String::from(v), self.strings.len());
+			self.serialize_usize(0)?;
+		}
+		if self.config.string_type == StringType::Utf16 {
+			let units: Vec<u16> = v.encode_utf16().collect();
+			self.serialize_usize(units.len())?;
+			for unit in units {
+				self.serialize_num(unit)?;
+			}
+			return Ok(());
+		}
+		self.serialize_usize(v.len())?;
+		self.serialize_slice(v.as_bytes())?;
+		if self.config.string_type == StringType::SizeTaggedAndNullTerminated {
+			self.serialize_slice(&[0x00])?;
+		}
+		Ok(())
 	}
 
-	fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
-		unimplemented!()
+	fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+		self.tag(tags::BYTES)?;
+		self.serialize_usize(v.len())?;
+		self.serialize_slice(v)
 	}
 
 	fn serialize_none(self) -> Result<Self::Ok> {
-		self.serialize_u8(flags::NONE)
+		self.serialize_num(flags::NONE)
 	}
 
 	fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
 	where
 		T: ?Sized + ser::Serialize,
 	{
-		self.serialize_u8(flags::SOME).unwrap();
+		self.serialize_num(flags::SOME)?;
 		value.serialize(self)
 	}
 
 	fn serialize_unit(self) -> Result<Self::Ok> {
-		Ok(())
+		self.tag(tags::UNIT)
 	}
 
 	fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
-		Ok(())
+		self.tag(tags::UNIT)
 	}
 
 	fn serialize_unit_variant(
 		self,
 		_name: &'static str,
 		variant_index: u32,
-		_variant: &'static str,
+		variant: &'static str,
 	) -> Result<Self::Ok> {
-		UNIT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self)
+		self.serialize_variant(UNIT_VARIANT, variant_index, variant)
 	}
 
-	fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+	fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
 	where
 		T: ?Sized + ser::Serialize,
 	{
+		if self.config.newtype_names {
+			self.tag(tags::NEWTYPE)?;
+			ser::Serializer::serialize_str(&mut *self, name)?;
+		}
 		value.serialize(self)
 	}
 
@@ -175,66 +463,92 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 		self,
 		_name: &'static str,
 		variant_index: u32,
-		_variant: &'static str,
+		variant: &'static str,
 		value: &T,
 	) -> Result<Self::Ok>
 	where
 		T: ?Sized + ser::Serialize,
 	{
-		NONUNIT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self).unwrap();
+		self.serialize_variant(NONUNIT_VARIANT, variant_index, variant)?;
 		value.serialize(self)
 	}
 
 	fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-		match len {
-			Some(n) => {
-				self.serialize_usize(n).unwrap();
-			}
-			None => unimplemented!(),
+		// Sequences of unknown length are written once all the elements are known, since any
+		// value that could mark the end of the sequence could also be an element
+		self.tag(tags::SEQ)?;
+		self.start_collection(len)?;
+		if self.config.validate_element_size && !self.config.self_describing {
+			// Filled in once the first element shows whether it is a number
+			self.element_sizes.push(Some(self.output.len()));
+			self.output.push(0x00);
 		}
 		Ok(self)
 	}
 
 	fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-		self.serialize_seq(Some(len))
+		if self.config.omit_tuple_lengths && !self.config.self_describing {
+			self.collections.push(None);
+			return Ok(self);
+		}
+		self.tag(tags::SEQ)?;
+		self.start_collection(Some(len))?;
+		Ok(self)
 	}
 
 	fn serialize_tuple_struct(
 		self,
-		_name: &'static str,
+		name: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeTupleStruct> {
-		self.serialize_seq(Some(len))
+		// A top level tuple struct has no field names, so only its name is part of the fingerprint
+		if self.config.fingerprint && self.struct_depth == 0 && self.output.is_empty() {
+			self.schema = Some(vec![name]);
+		}
+		self.struct_depth += 1;
+		self.tag(tags::SEQ)?;
+		self.start_collection(Some(len))?;
+		Ok(self)
 	}
 
 	fn serialize_tuple_variant(
 		self,
 		_name: &'static str,
 		variant_index: u32,
-		_variant: &'static str,
+		variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeTupleVariant> {
-		NONUNIT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self).unwrap();
-		self.serialize_usize(len).unwrap();
+		self.serialize_variant(NONUNIT_VARIANT, variant_index, variant)?;
+		self.tag(tags::SEQ)?;
+		self.serialize_usize(len)?;
 		Ok(self)
 	}
 
 	fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-		match len {
-			Some(n) => {
-				self.serialize_vec(compress_usize(n)).unwrap();
-				Ok(self)
-			}
-			// Serializing maps of unknown length to binary is difficult, since any value that
-			// can be used to mark the end of the sequence can also be a member
-			None => unimplemented!(),
+		self.tag(tags::MAP)?;
+		self.start_collection(len)?;
+		if self.config.canonical {
+			self.map_entries.push(Vec::new());
 		}
+		Ok(self)
 	}
 
-	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-		self.serialize_usize(len).unwrap();
+	fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+		if self.config.fingerprint && self.struct_depth == 0 && self.output.is_empty() {
+			self.schema = Some(vec![name]);
+		}
+		self.struct_depth += 1;
+		self.tag(tags::MAP)?;
+		self.start_fields(len)?;
+		if self.config.bitmap_options && !self.config.self_describing {
+			self.bitmaps.push(Bitmap {
+				position: self.output.len(),
+				len,
+				field: 0,
+			});
+			self.output
+				.resize(self.output.len() + len.div_ceil(8), 0x00);
+		}
 		Ok(self)
 	}
 
@@ -242,17 +556,22 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 		self,
 		_name: &'static str,
 		variant_index: u32,
-		_variant: &'static str,
+		variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeStructVariant> {
-		STRUCT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self).unwrap();
-		self.serialize_usize(len).unwrap();
+		self.serialize_variant(STRUCT_VARIANT, variant_index, variant)?;
+		self.tag(tags::MAP)?;
+		self.start_fields(len)?;
 		Ok(self)
 	}
+
+	// Must match the Deserializer, or types that branch on this will not round trip
+	fn is_human_readable(&self) -> bool {
+		false
+	}
 }
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl ser::SerializeSeq for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -260,16 +579,30 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
 	where
 		T: ?Sized + Serialize,
 	{
-		value.serialize(&mut **self)
+		self.count_entry();
+		let Some(Some(position)) = self.element_sizes.last_mut().map(Option::take) else {
+			return value.serialize(&mut **self);
+		};
+		let start = self.output.len();
+		value.serialize(&mut **self)?;
+		// Only an element that is a single number has a size to check
+		let (number_start, number_end) = self.last_number;
+		if number_start == start && number_end == self.output.len() {
+			self.output[position] = (number_end - number_start) as u8;
+		}
+		Ok(())
 	}
 
 	// Close the sequence.
 	fn end(self) -> Result<()> {
-		Ok(())
+		if self.config.validate_element_size && !self.config.self_describing {
+			self.element_sizes.pop();
+		}
+		self.end_collection()
 	}
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl ser::SerializeTuple for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -277,15 +610,16 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
 	where
 		T: ?Sized + Serialize,
 	{
+		self.count_entry();
 		value.serialize(&mut **self)
 	}
 
 	fn end(self) -> Result<()> {
-		Ok(())
+		self.end_collection()
 	}
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -293,15 +627,17 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
 	where
 		T: ?Sized + Serialize,
 	{
+		self.count_entry();
 		value.serialize(&mut **self)
 	}
 
 	fn end(self) -> Result<()> {
-		Ok(())
+		self.struct_depth -= 1;
+		self.end_collection()
 	}
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -317,7 +653,7 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
 	}
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+impl ser::SerializeMap for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -325,6 +661,15 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
 	where
 		T: ?Sized + Serialize,
 	{
+		self.count_entry();
+		if self.config.canonical
+			&& let Some(entries) = self.map_entries.last_mut()
+		{
+			entries.push(self.output.len());
+		}
+		if self.framed_map_entries() {
+			self.map_frames.push(self.output.len());
+		}
 		key.serialize(&mut **self)
 	}
 
@@ -332,42 +677,211 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
 	where
 		T: ?Sized + Serialize,
 	{
-		value.serialize(&mut **self)
+		value.serialize(&mut **self)?;
+		// The length of the entry is inserted before its key, now that it is known
+		if self.framed_map_entries()
+			&& let Some(start) = self.map_frames.pop()
+		{
+			let len = compress_usize_as(self.output.len() - start, self.config.varint_endianness);
+			self.output.splice(start..start, len);
+		}
+		Ok(())
 	}
 
 	fn end(self) -> Result<()> {
-		Ok(())
+		if self.config.canonical
+			&& let Some(entries) = self.map_entries.pop()
+		{
+			self.sort_entries(entries);
+		}
+		self.end_collection()
 	}
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl ser::SerializeStruct for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
-	fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+	fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
 	where
 		T: ?Sized + Serialize,
 	{
-		value.serialize(&mut **self)
+		if self.struct_depth == 1
+			&& let Some(schema) = self.schema.as_mut()
+		{
+			schema.push(key);
+		}
+		self.count_field();
+		if self.config.self_describing {
+			ser::Serializer::serialize_str(&mut **self, key)?;
+			return value.serialize(&mut **self);
+		}
+		if !self.config.bitmap_options {
+			return value.serialize(&mut **self);
+		}
+
+		value.serialize(FieldSerializer::new(self))?;
+		let bitmap = match self.bitmaps.last_mut() {
+			Some(v) => v,
+			None => return Err(BinaryError::UnexpectedType),
+		};
+		if bitmap.field >= bitmap.len {
+			return Err(BinaryError::InvalidLength {
+				actual: bitmap.field + 1,
+				expected: bitmap.len,
+			});
+		}
+		if self.field_present {
+			self.output[bitmap.position + bitmap.field / 8] |= 1 << (bitmap.field % 8);
+		}
+		bitmap.field += 1;
+		Ok(())
 	}
 
 	fn end(self) -> Result<()> {
+		let bitmap = self.config.bitmap_options && !self.config.self_describing;
+		if bitmap {
+			self.bitmaps.pop();
+		}
+		self.struct_depth -= 1;
+		self.end_fields(bitmap);
 		Ok(())
 	}
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer {
 	type Ok = ();
 	type Error = BinaryError;
 
-	fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+	fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
 	where
 		T: ?Sized + Serialize,
 	{
+		self.count_field();
+		if self.config.self_describing {
+			ser::Serializer::serialize_str(&mut **self, key)?;
+		}
 		value.serialize(&mut **self)
 	}
 
 	fn end(self) -> Result<()> {
+		self.end_fields(false);
 		Ok(())
 	}
 }
+
+macro_rules! forward_present {
+	($($name:ident($($arg:ident: $ty:ty),*) -> $ok:ty;)*) => {
+		$(
+			fn $name(self, $($arg: $ty),*) -> Result<$ok> {
+				self.ser.field_present = true;
+				self.ser.$name($($arg),*)
+			}
+		)*
+	};
+}
+
+/// Serializes a single struct field when presence bitmaps are enabled, omitting `None` values
+/// and the `SOME` flag and recording whether the field was present
+struct FieldSerializer<'a> {
+	ser: &'a mut Serializer,
+}
+
+impl<'a> FieldSerializer<'a> {
+	fn new(ser: &'a mut Serializer) -> Self {
+		Self { ser }
+	}
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+	type Ok = ();
+	type Error = BinaryError;
+
+	type SerializeSeq = &'a mut Serializer;
+	type SerializeTuple = &'a mut Serializer;
+	type SerializeTupleStruct = &'a mut Serializer;
+	type SerializeTupleVariant = &'a mut Serializer;
+	type SerializeMap = &'a mut Serializer;
+	type SerializeStruct = &'a mut Serializer;
+	type SerializeStructVariant = &'a mut Serializer;
+
+	forward_present! {
+		serialize_bool(v: bool) -> ();
+		serialize_u8(v: u8) -> ();
+		serialize_u16(v: u16) -> ();
+		serialize_u32(v: u32) -> ();
+		serialize_u64(v: u64) -> ();
+		serialize_u128(v: u128) -> ();
+		serialize_i8(v: i8) -> ();
+		serialize_i16(v: i16) -> ();
+		serialize_i32(v: i32) -> ();
+		serialize_i64(v: i64) -> ();
+		serialize_i128(v: i128) -> ();
+		serialize_f32(v: f32) -> ();
+		serialize_f64(v: f64) -> ();
+		serialize_char(v: char) -> ();
+		serialize_str(v: &str) -> ();
+		serialize_bytes(v: &[u8]) -> ();
+		serialize_unit() -> ();
+		serialize_unit_struct(name: &'static str) -> ();
+		serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str) -> ();
+		serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+		serialize_tuple(len: usize) -> Self::SerializeTuple;
+		serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+		serialize_tuple_variant(
+			name: &'static str,
+			variant_index: u32,
+			variant: &'static str,
+			len: usize
+		) -> Self::SerializeTupleVariant;
+		serialize_map(len: Option<usize>) -> Self::SerializeMap;
+		serialize_struct(name: &'static str, len: usize) -> Self::SerializeStruct;
+		serialize_struct_variant(
+			name: &'static str,
+			variant_index: u32,
+			variant: &'static str,
+			len: usize
+		) -> Self::SerializeStructVariant;
+	}
+
+	fn serialize_none(self) -> Result<Self::Ok> {
+		self.ser.field_present = false;
+		Ok(())
+	}
+
+	fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
+	where
+		T: ?Sized + ser::Serialize,
+	{
+		self.ser.field_present = true;
+		value.serialize(self.ser)
+	}
+
+	fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
+	where
+		T: ?Sized + ser::Serialize,
+	{
+		self.ser.field_present = true;
+		self.ser.serialize_newtype_struct(name, value)
+	}
+
+	fn serialize_newtype_variant<T>(
+		self,
+		name: &'static str,
+		variant_index: u32,
+		variant: &'static str,
+		value: &T,
+	) -> Result<Self::Ok>
+	where
+		T: ?Sized + ser::Serialize,
+	{
+		self.ser.field_present = true;
+		self.ser
+			.serialize_newtype_variant(name, variant_index, variant, value)
+	}
+
+	// Fields must be written in the same form as other values
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
@@ -0,0 +1,157 @@
+//! Serialize signed integers in sign-magnitude form, for formats that do not use two's complement.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::sign_magnitude")]` on an `i16`, `i32` or `i64`
+//! field. The value is written as an unsigned integer of the same width, in the byte order of
+//! numbers, whose highest bit is set for negative values and whose other bits hold the magnitude,
+//! so `-1_i16` is written as `0x8001`.
+//!
+//! The minimum value of each type, such as `i32::MIN`, has no magnitude that fits, so writing it
+//! is an error. Reading a negative zero, whose only bit set is the sign, gives `0`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned, ser};
+
+/// A signed integer type that can be written in sign-magnitude form
+pub trait SignMagnitudeInteger: Copy {
+	/// The unsigned integer of the same width that holds the sign and magnitude
+	type Unsigned: Serialize + DeserializeOwned;
+
+	/// Returns the sign and magnitude, or `None` for the minimum value
+	fn to_sign_magnitude(self) -> Option<Self::Unsigned>;
+
+	/// Returns the value of a sign and magnitude
+	fn from_sign_magnitude(value: Self::Unsigned) -> Self;
+}
+
+macro_rules! impl_sign_magnitude {
+	($($signed:ty => $unsigned:ty),*) => {
+		$(
+			impl SignMagnitudeInteger for $signed {
+				type Unsigned = $unsigned;
+
+				fn to_sign_magnitude(self) -> Option<$unsigned> {
+					const SIGN: $unsigned = !(<$unsigned>::MAX >> 1);
+					match self {
+						<$signed>::MIN => None,
+						value if value < 0 => Some(value.unsigned_abs() | SIGN),
+						value => Some(value.unsigned_abs()),
+					}
+				}
+
+				fn from_sign_magnitude(value: $unsigned) -> Self {
+					const SIGN: $unsigned = !(<$unsigned>::MAX >> 1);
+					let magnitude = (value & !SIGN) as $signed;
+					if value & SIGN != 0 { -magnitude } else { magnitude }
+				}
+			}
+		)*
+	};
+}
+
+impl_sign_magnitude!(i16 => u16, i32 => u32, i64 => u64);
+
+/// Serializes a signed integer in sign-magnitude form
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: SignMagnitudeInteger,
+{
+	let value = value.to_sign_magnitude().ok_or_else(|| {
+		ser::Error::custom("the minimum value has no magnitude in sign-magnitude form")
+	})?;
+	value.serialize(serializer)
+}
+
+/// Deserializes a signed integer from sign-magnitude form
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: SignMagnitudeInteger,
+{
+	T::Unsigned::deserialize(deserializer).map(T::from_sign_magnitude)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+	struct Signed {
+		#[serde(with = "crate::sign_magnitude")]
+		pub short: i16,
+		#[serde(with = "crate::sign_magnitude")]
+		pub int: i32,
+		#[serde(with = "crate::sign_magnitude")]
+		pub long: i64,
+	}
+
+	impl Signed {
+		fn new(value: i16) -> Self {
+			Self {
+				short: value,
+				int: value.into(),
+				long: value.into(),
+			}
+		}
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let values = [
+			Signed::new(0),
+			Signed::new(1),
+			Signed::new(-1),
+			Signed::new(i16::MAX),
+			Signed::new(-i16::MAX),
+			Signed {
+				int: i32::MAX,
+				long: -i64::MAX,
+				..Signed::new(0)
+			},
+		];
+		for value in values {
+			for big_endian in [false, true] {
+				let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+				let deserialized: Signed =
+					Deserializer::from_bytes(&serialized, big_endian).unwrap();
+				assert_eq!(value, deserialized);
+			}
+		}
+
+		// Struct length, then the magnitude with the sign in the highest bit of each field
+		let serialized = Serializer::to_bytes(&Signed::new(-2), true).unwrap();
+		assert_eq!(
+			serialized,
+			vec![
+				0x03, 0x80, 0x02, 0x80, 0x00, 0x00, 0x02, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+				0x02
+			]
+		);
+	}
+
+	#[test]
+	fn test_min() {
+		for value in [
+			Signed::new(i16::MIN),
+			Signed {
+				int: i32::MIN,
+				..Signed::new(0)
+			},
+			Signed {
+				long: i64::MIN,
+				..Signed::new(0)
+			},
+		] {
+			assert!(Serializer::to_bytes(&value, false).is_err());
+		}
+	}
+
+	#[test]
+	fn test_negative_zero() {
+		let mut serialized = Serializer::to_bytes(&Signed::new(0), true).unwrap();
+		serialized[1] = 0x80;
+		let deserialized: Signed = Deserializer::from_bytes(&serialized, true).unwrap();
+		assert_eq!(deserialized, Signed::new(0));
+	}
+}
@@ -2,36 +2,74 @@
 //! use with streams that implement Read/Write
 
 mod de;
+mod limitedwriter;
+mod seq;
 mod ser;
 
 pub use de::Deserializer;
+pub use limitedwriter::LimitedWriter;
+pub use seq::{SeqReader, SeqWriter};
 pub use ser::Serializer;
 
 #[cfg(test)]
 mod tests {
-	use std::collections::HashMap;
+	use std::{
+		borrow::Cow,
+		collections::{BTreeMap, HashMap},
+		io::{Cursor, Read, Write},
+		marker::PhantomData,
+		path::PathBuf,
+		thread,
+	};
 
 	use super::de::Deserializer;
 	use super::ser::Serializer;
+	use crate::BinaryError;
+	use crate::serde_binary_adv::common::compress_usize;
 
 	use serde::{Deserialize, Serialize};
+	use serde_bytes::ByteBuf;
+	use static_assertions::{assert_impl_all, assert_not_impl_any};
+	use uuid::Uuid;
+
+	assert_impl_all!(Serializer<Vec<u8>>: Send);
+	assert_impl_all!(Serializer<&'static mut (dyn Write + Send)>: Send);
+	assert_not_impl_any!(Serializer<&'static mut dyn Write>: Send);
+	assert_impl_all!(Deserializer<'static, dyn Read + Send>: Send);
+	assert_not_impl_any!(Deserializer<'static, dyn Read>: Send);
 
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
 	struct Unit;
 
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Empty {}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Single {
+		pub byte: u8,
+	}
+
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
 	struct NewType(u8);
 
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
 	struct TupleStruct(u8, u8, u8);
 
-	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
 	struct Test {
 		pub byte: u8,
 		pub string: String,
 	}
 
 	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Phantom {
+		pub byte: u8,
+		pub marker: PhantomData<String>,
+		pub string: String,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	#[allow(clippy::enum_variant_names)]
 	enum TestEnum {
 		NewTypeVariant(u8),
 		StructVariant { a: u8, b: u8 },
@@ -62,17 +100,19 @@ mod tests {
 	impl_test_x!(test_bool_true, bool, true);
 	impl_test_x!(test_bool_false, bool, false);
 
-	impl_test_x!(test_u8, u8, 0x41 as u8);
-	impl_test_x!(test_u16, u16, 0x41 as u16);
-	impl_test_x!(test_u32, u32, 0x41 as u32);
-	impl_test_x!(test_u64, u64, 0x41 as u64);
-	impl_test_x!(test_u128, u128, 0x41 as u128);
+	impl_test_x!(test_u8, u8, 0x41_u8);
+	impl_test_x!(test_u16, u16, 0x41_u16);
+	impl_test_x!(test_u32, u32, 0x41_u32);
+	impl_test_x!(test_u64, u64, 0x41_u64);
+	impl_test_x!(test_u128, u128, 0x41_u128);
 
-	impl_test_x!(test_i8, i8, 0x41 as i8);
-	impl_test_x!(test_i16, i16, 0x41 as i16);
-	impl_test_x!(test_i32, i32, 0x41 as i32);
-	impl_test_x!(test_i64, i64, 0x41 as i64);
-	impl_test_x!(test_i128, i128, 0x41 as i128);
+	impl_test_x!(test_i8, i8, 0x41_i8);
+	impl_test_x!(test_i16, i16, 0x41_i16);
+	impl_test_x!(test_i32, i32, 0x41_i32);
+	impl_test_x!(test_i64, i64, 0x41_i64);
+	impl_test_x!(test_i128, i128, 0x41_i128);
+	impl_test_x!(test_u128_max, u128, u128::MAX);
+	impl_test_x!(test_i128_min, i128, i128::MIN);
 
 	impl_test_x!(test_f32, f32, 0x41 as f32);
 	impl_test_x!(test_f64, f64, 0x41 as f64);
@@ -84,9 +124,9 @@ mod tests {
 
 	#[test]
 	fn test_bad_char() {
-		assert!(Deserializer::read_bytes::<char>(&mut vec![0x80 as u8].as_slice(), false).is_err());
+		assert!(Deserializer::read_bytes::<char>(&mut vec![0x80_u8].as_slice(), false).is_err());
 		assert!(
-			Deserializer::read_bytes::<char>(&mut vec![0xC0 as u8, 0x00 as u8].as_slice(), false)
+			Deserializer::read_bytes::<char>(&mut vec![0xC0_u8, 0x00_u8].as_slice(), false)
 				.is_err()
 		);
 		assert!(Deserializer::read_bytes::<char>(&mut vec![].as_slice(), false).is_err());
@@ -95,15 +135,91 @@ mod tests {
 	// Test Serde String
 	impl_test_x!(test_string, String, String::from("test"));
 
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Borrowing<'a> {
+		#[serde(borrow)]
+		pub text: Cow<'a, str>,
+		#[serde(borrow)]
+		pub bytes: Cow<'a, [u8]>,
+	}
+
+	#[test]
+	fn test_owned_cow() {
+		let value = Borrowing {
+			text: Cow::Borrowed("test"),
+			bytes: Cow::Borrowed(&[0x41, 0x42, 0x43]),
+		};
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &value, false).unwrap();
+		let mut input = buf.as_slice();
+		let deserialized: Borrowing = Deserializer::read_bytes(&mut input, false).unwrap();
+		assert_eq!(value, deserialized);
+		// A reader can't lend out its contents, so the values are always copied
+		assert!(matches!(deserialized.text, Cow::Owned(_)));
+		assert!(matches!(deserialized.bytes, Cow::Owned(_)));
+	}
+
 	// Test Serde Option
 	impl_test_x!(test_none, Option<u64>, None::<u64>);
 	impl_test_x!(test_some, Option<i32>, Some(0x41));
+	impl_test_x!(test_none_string, Option<String>, None::<String>);
+	impl_test_x!(test_some_string, Option<String>, Some(String::from("test")));
+	impl_test_x!(test_some_empty_string, Option<String>, Some(String::new()));
+	impl_test_x!(
+		test_some_vec,
+		Option<Vec<u8>>,
+		Some(vec![0x41_u8, 0x42, 0x43])
+	);
+	impl_test_x!(test_some_empty_vec, Option<Vec<u8>>, Some(Vec::<u8>::new()));
+	impl_test_x!(test_some_zero, Option<u8>, Some(0x00_u8));
+	impl_test_x!(test_some_max, Option<u8>, Some(0xFF_u8));
+	impl_test_x!(test_none_u8, Option<u8>, None::<u8>);
+
+	#[test]
+	fn test_some_flag_values() {
+		// The stream reads the same bytes as the in-memory Deserializer
+		for value in [Some(0x00_u8), Some(0xFF_u8), None] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &value, false).unwrap();
+			assert_eq!(*buf, crate::Serializer::to_bytes(&value, false).unwrap());
+			let values = vec![value, Some(0xFF), None, Some(0x00)];
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &values, false).unwrap();
+			let deserialized: Vec<Option<u8>> =
+				Deserializer::read_bytes(&mut buf.as_slice(), false).unwrap();
+			assert_eq!(values, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_invalid_option_flag() {
+		assert_eq!(
+			Deserializer::read_bytes::<Option<u8>>(&mut [0x7F_u8, 0x41].as_slice(), false),
+			Err(BinaryError::InvalidOptionFlag { actual: 0x7F })
+		);
+	}
 
 	// Test Serde Units
 	impl_test_x!(test_unit, (), ());
 
 	impl_test_x!(test_unit_struct, Unit, Unit {});
 
+	impl_test_x!(test_empty_struct, Empty, Empty {});
+
+	impl_test_x!(test_single_field_struct, Single, Single { byte: 0x41 });
+
+	impl_test_x!(test_empty_structs, Vec<Empty>, vec![Empty {}, Empty {}]);
+
+	impl_test_x!(
+		test_phantom_data,
+		Phantom,
+		Phantom {
+			byte: 0x41,
+			marker: PhantomData,
+			string: String::from("test"),
+		}
+	);
+
 	// Test Serde Variants
 	impl_test_x!(test_unit_variant, TestEnum, TestEnum::UnitVariant);
 	impl_test_x!(
@@ -117,6 +233,30 @@ mod tests {
 		TestEnum::TupleVariant(0x41, 0x42, 0x43)
 	);
 
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Extended {
+		A,
+		B,
+		C,
+		D,
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	enum Known {
+		A,
+		B,
+		#[serde(other)]
+		Unknown,
+	}
+
+	#[test]
+	fn test_other_variant() {
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &Extended::D, false).unwrap();
+		let deserialized: Known = Deserializer::read_bytes(&mut buf.as_slice(), false).unwrap();
+		assert_eq!(deserialized, Known::Unknown);
+	}
+
 	// Test Serde Structs
 	impl_test_x!(
 		test_struct,
@@ -139,12 +279,8 @@ mod tests {
 	);
 
 	// Test Serde sequences
-	impl_test_x!(test_vec, Vec<u8>, vec![0x41 as u8, 0x42, 0x43]);
-	impl_test_x!(
-		test_byte_array,
-		[u8; 3],
-		[0x41 as u8, 0x42 as u8, 0x43 as u8]
-	);
+	impl_test_x!(test_vec, Vec<u8>, vec![0x41_u8, 0x42, 0x43]);
+	impl_test_x!(test_byte_array, [u8; 3], [0x41_u8, 0x42_u8, 0x43_u8]);
 
 	#[test]
 	fn test_array() {
@@ -178,6 +314,524 @@ mod tests {
 		assert_eq!(v, deserialized2);
 	}
 
+	#[test]
+	fn test_map_struct_values() {
+		let mut v: HashMap<String, Test> = HashMap::new();
+		v.insert(
+			String::from("a"),
+			Test {
+				byte: 0x41,
+				string: String::from("first"),
+			},
+		);
+		v.insert(
+			String::from("b"),
+			Test {
+				byte: 0x42,
+				string: String::new(),
+			},
+		);
+		for big_endian in [false, true] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &v, big_endian).unwrap();
+			let deserialized: HashMap<String, Test> =
+				Deserializer::read_bytes(&mut buf.as_slice(), big_endian).unwrap();
+			assert_eq!(v, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_map_vec_values() {
+		let mut v: HashMap<u32, Vec<u8>> = HashMap::new();
+		v.insert(0x41, vec![0x41, 0x42, 0x43]);
+		v.insert(0x42, vec![]);
+		v.insert(0x43, vec![0xFF; 200]);
+		for big_endian in [false, true] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &v, big_endian).unwrap();
+			let deserialized: HashMap<u32, Vec<u8>> =
+				Deserializer::read_bytes(&mut buf.as_slice(), big_endian).unwrap();
+			assert_eq!(v, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_unit_map() {
+		let mut values: HashMap<u8, ()> = HashMap::new();
+		values.insert(0x41, ());
+		values.insert(0x42, ());
+		let mut keys: BTreeMap<(), u32> = BTreeMap::new();
+		keys.insert((), 0x41);
+		let v = (values, keys, 0x43_u8);
+		for big_endian in [false, true] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &v, big_endian).unwrap();
+			let deserialized: (HashMap<u8, ()>, BTreeMap<(), u32>, u8) =
+				Deserializer::read_bytes(&mut buf.as_slice(), big_endian).unwrap();
+			assert_eq!(v, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_struct_keys() {
+		let mut v: BTreeMap<Test, u32> = BTreeMap::new();
+		for byte in 0x41..0x45_u8 {
+			let key = Test {
+				byte,
+				string: String::from(char::from(byte)).repeat(usize::from(byte - 0x40)),
+			};
+			v.insert(key, u32::from(byte));
+		}
+		for big_endian in [false, true] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &v, big_endian).unwrap();
+			let deserialized: BTreeMap<Test, u32> =
+				Deserializer::read_bytes(&mut buf.as_slice(), big_endian).unwrap();
+			assert_eq!(v, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_uuid() {
+		let value = Uuid::from_u128(0x0102_0304_0506_0708_090A_0B0C_0D0E_0F10);
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &value, false).unwrap();
+		assert_eq!(buf.len(), 17);
+		assert_eq!(&buf[1..], value.as_bytes());
+		let deserialized: Uuid = Deserializer::read_bytes(&mut buf.as_slice(), false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	impl_test_x!(test_path, PathBuf, PathBuf::from("/usr/local/bin"));
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	#[serde(untagged)]
+	enum Untagged {
+		Number(u64),
+		Text(String),
+	}
+
+	#[test]
+	fn test_untagged() {
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &Untagged::Number(0x41), false).unwrap();
+		assert!(matches!(
+			Deserializer::read_bytes::<Untagged>(&mut buf.as_slice(), false),
+			Err(BinaryError::Message { .. })
+		));
+	}
+
+	// Counts the calls made to the inner reader
+	struct CountingReader<'a> {
+		input: &'a [u8],
+		reads: usize,
+	}
+
+	impl Read for CountingReader<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			self.reads += 1;
+			self.input.read(buf)
+		}
+	}
+
+	#[test]
+	fn test_byte_buf() {
+		let value = ByteBuf::from(vec![0xA5_u8; 0x10000]);
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &value, false).unwrap();
+		// The same layout as a sequence of u8
+		let seq: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(seq, &value.to_vec(), false).unwrap();
+		assert_eq!(buf, seq);
+
+		let mut reader = CountingReader {
+			input: buf.as_slice(),
+			reads: 0,
+		};
+		let deserialized: ByteBuf = Deserializer::read_bytes(&mut reader, false).unwrap();
+		assert_eq!(value, deserialized);
+		// The length is read a byte at a time, the contents in one go
+		assert!(reader.reads <= 4);
+
+		let mut truncated = &buf[..0x100];
+		assert_eq!(
+			Deserializer::read_bytes::<ByteBuf>(&mut truncated, false),
+			Err(BinaryError::UnexpectedEndOfInput)
+		);
+
+		// A length far beyond the data is not allocated up front
+		let serialized = compress_usize(usize::MAX);
+		assert_eq!(
+			Deserializer::read_bytes::<ByteBuf>(&mut serialized.as_slice(), false),
+			Err(BinaryError::UnexpectedEndOfInput)
+		);
+	}
+
 	// Test Serde Tuple
-	impl_test_x!(test_tuple, (char, i32, u8), ('a', 16, 0x41 as u8));
+	impl_test_x!(test_tuple, (char, i32, u8), ('a', 16, 0x41_u8));
+
+	// Test references and slices at the top level
+	#[test]
+	fn test_references() {
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		let buf2: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &&5_u32, false).unwrap();
+		Serializer::write_bytes(buf2, &5_u32, false).unwrap();
+		assert_eq!(buf, buf2);
+
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		let buf2: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &[1_u8, 2, 3][..], false).unwrap();
+		Serializer::write_bytes(buf2, &vec![1_u8, 2, 3], false).unwrap();
+		assert_eq!(buf, buf2);
+
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		let buf2: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &"hello", false).unwrap();
+		Serializer::write_bytes(buf2, &String::from("hello"), false).unwrap();
+		assert_eq!(buf, buf2);
+	}
+
+	#[test]
+	fn test_owned_writer() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		let mut serializer = Serializer::from_writer(Vec::new(), false);
+		serializer.serialize(&value).unwrap();
+		serializer.serialize(&0x44_u8).unwrap();
+		let buf = serializer.into_inner();
+
+		let expected: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(expected, &value, false).unwrap();
+		expected.push(0x44);
+		assert_eq!(&buf, expected);
+		let deserialized: Test = Deserializer::read_bytes(&mut buf.as_slice(), false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	#[test]
+	fn test_cursor() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		let mut buf = [0x00_u8; 64];
+		let mut cursor = Cursor::new(&mut buf);
+		let position = Serializer::write_to_cursor(&mut cursor, &value, false).unwrap();
+		assert_eq!(position, 6);
+		let position = Serializer::write_to_cursor(&mut cursor, &value, false).unwrap();
+		assert_eq!(position, 12);
+		let mut input = &buf[..12];
+		for _ in 0..2 {
+			let deserialized: Test = Deserializer::read_bytes(&mut input, false).unwrap();
+			assert_eq!(value, deserialized);
+		}
+
+		// A slice that is too short
+		let mut buf = [0x00_u8; 4];
+		let mut cursor: Cursor<&mut [u8]> = Cursor::new(&mut buf);
+		assert!(Serializer::write_to_cursor(&mut cursor, &value, false).is_err());
+		assert_eq!(cursor.position(), 4);
+	}
+
+	#[test]
+	fn test_seq_size_hint() {
+		let value: Vec<u64> = (0..10_000).collect();
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &value, false).unwrap();
+		let deserialized: Vec<u64> = Deserializer::read_bytes(&mut buf.as_slice(), false).unwrap();
+		assert_eq!(deserialized, value);
+		assert_eq!(deserialized.capacity(), 10_000);
+	}
+
+	// Returns the data in chunks, with no data ready before the chunk after the first
+	struct NonBlockingReader<'a> {
+		chunks: Vec<&'a [u8]>,
+		blocked: bool,
+	}
+
+	impl Read for NonBlockingReader<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			if self.blocked {
+				self.blocked = false;
+				return Err(std::io::ErrorKind::WouldBlock.into());
+			}
+			let Some(chunk) = self.chunks.first_mut() else {
+				return Ok(0);
+			};
+			let len = buf.len().min(chunk.len());
+			buf[..len].copy_from_slice(&chunk[..len]);
+			*chunk = &chunk[len..];
+			if chunk.is_empty() {
+				self.chunks.remove(0);
+				self.blocked = !self.chunks.is_empty();
+			}
+			Ok(len)
+		}
+	}
+
+	#[test]
+	fn test_would_block() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &value, false).unwrap();
+
+		let (first, second) = buf.split_at(3);
+		let mut reader = NonBlockingReader {
+			chunks: vec![first, second],
+			blocked: false,
+		};
+		assert_eq!(
+			Deserializer::read_bytes::<Test>(&mut reader, false),
+			Err(BinaryError::WouldBlock)
+		);
+		// Once data is ready again, the rest of the input is there to be read
+		let mut rest = Vec::new();
+		reader.read_to_end(&mut rest).unwrap();
+		assert_eq!(rest, second);
+
+		let mut reader = NonBlockingReader {
+			chunks: vec![buf.as_slice()],
+			blocked: false,
+		};
+		let deserialized: Test = Deserializer::read_bytes(&mut reader, false).unwrap();
+		assert_eq!(value, deserialized);
+	}
+
+	// Accepts a number of bytes, then fails every write like a closed pipe
+	struct FailingWriter {
+		remaining: usize,
+	}
+
+	impl Write for FailingWriter {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			if self.remaining == 0 {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::BrokenPipe,
+					"pipe closed",
+				));
+			}
+			let len = buf.len().min(self.remaining);
+			self.remaining -= len;
+			Ok(len)
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_io_error() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		for remaining in 0..5 {
+			let mut writer = FailingWriter { remaining };
+			assert_eq!(
+				Serializer::write_bytes(&mut writer, &value, false),
+				Err(BinaryError::Message {
+					message: String::from("pipe closed")
+				})
+			);
+		}
+		let mut writer = FailingWriter { remaining: 6 };
+		assert!(Serializer::write_bytes(&mut writer, &value, false).is_ok());
+	}
+
+	#[test]
+	fn test_thread() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		let expected: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(expected, &value, false).unwrap();
+
+		let mut serializer = Serializer::from_writer(Vec::new(), false);
+		let buf = thread::spawn(move || {
+			serializer.serialize(&value).unwrap();
+			serializer.into_inner()
+		})
+		.join()
+		.unwrap();
+		assert_eq!(&buf, expected);
+
+		let mut buf: Vec<u8> = Vec::new();
+		let output: &mut (dyn Write + Send) = &mut buf;
+		let mut serializer = Serializer::new(output, false);
+		thread::scope(|scope| scope.spawn(move || serializer.serialize(&0x44_u8)).join())
+			.unwrap()
+			.unwrap();
+		assert_eq!(buf, vec![0x44]);
+
+		let mut reader = expected.as_slice();
+		let input: &mut (dyn Read + Send) = &mut reader;
+		let mut deserializer = Deserializer::new(input, false);
+		let value: Test = thread::scope(|scope| {
+			scope
+				.spawn(move || Test::deserialize(&mut deserializer))
+				.join()
+		})
+		.unwrap()
+		.unwrap();
+		assert_eq!(value.string, "ABC");
+	}
+
+	// Serializes as a sequence or map from an iterator with no upper bound on its length
+	struct Unsized(bool);
+
+	impl Serialize for Unsized {
+		fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			let values = (0_u8..).take_while(|v| *v < 4);
+			if self.0 {
+				serializer.collect_map(values.map(|v| (v, v)))
+			} else {
+				serializer.collect_seq(values)
+			}
+		}
+	}
+
+	#[test]
+	fn test_unknown_length() {
+		for map in [false, true] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			assert_eq!(
+				Serializer::write_bytes(buf, &Unsized(map), false),
+				Err(BinaryError::Message {
+					message: String::from(
+						"the length of a sequence or map must be known to write it to a stream"
+					)
+				})
+			);
+		}
+	}
+
+	#[test]
+	fn test_from_iter() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		for big_endian in [false, true] {
+			let buf: &mut Vec<u8> = &mut Vec::new();
+			Serializer::write_bytes(buf, &value, big_endian).unwrap();
+			let deserialized: Test = Deserializer::from_iter(buf.clone(), big_endian).unwrap();
+			assert_eq!(value, deserialized);
+
+			// Bytes after the value are left in the iterator
+			buf.push(0x44);
+			let mut iter = buf.clone().into_iter();
+			let deserialized: Test = Deserializer::from_iter(iter.by_ref(), big_endian).unwrap();
+			assert_eq!(value, deserialized);
+			assert_eq!(iter.next(), Some(0x44));
+
+			buf.truncate(buf.len() - 2);
+			assert!(matches!(
+				Deserializer::from_iter::<_, Test>(buf.clone(), big_endian),
+				Err(BinaryError::UnexpectedEndOfInput)
+			));
+		}
+	}
+
+	#[derive(Deserialize, Debug)]
+	enum Never {}
+
+	#[test]
+	fn test_empty_enum() {
+		let serialized = vec![0xFE_u8, 0x00, 0x00, 0x00, 0x00];
+		assert!(matches!(
+			Deserializer::read_bytes::<Never>(&mut serialized.as_slice(), false),
+			Err(BinaryError::Message { .. })
+		));
+	}
+
+	#[test]
+	fn test_end_marker() {
+		let value = Test {
+			byte: 0x41,
+			string: String::from("ABC"),
+		};
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes_with_end_marker(buf, &value, false).unwrap();
+		assert_eq!(buf.last(), Some(&0xFB));
+		let deserialized: Test =
+			Deserializer::read_bytes_with_end_marker(&mut buf.as_slice(), false).unwrap();
+		assert_eq!(value, deserialized);
+
+		// A stream cut off after the value, but before the marker, is detected as truncated
+		let truncated = &buf[..buf.len() - 1];
+		assert!(Deserializer::read_bytes::<Test>(&mut &truncated[..], false).is_ok());
+		assert_eq!(
+			Deserializer::read_bytes_with_end_marker::<Test>(&mut &truncated[..], false),
+			Err(BinaryError::UnexpectedEndOfInput)
+		);
+		let mut corrupted = buf.clone();
+		*corrupted.last_mut().unwrap() = 0x00;
+		assert_eq!(
+			Deserializer::read_bytes_with_end_marker::<Test>(&mut corrupted.as_slice(), false),
+			Err(BinaryError::MissingOrInvalidFlag {
+				actual: 0x00,
+				expected: 0xFB
+			})
+		);
+
+		// Values written one after another, each followed by the marker
+		let mut serializer = Serializer::from_writer(Vec::new(), true);
+		for i in 0..3_u16 {
+			serializer.serialize(&i).unwrap();
+			serializer.write_end_marker().unwrap();
+		}
+		let buf = serializer.into_inner();
+		let input = &mut buf.as_slice();
+		let mut deserializer = Deserializer::new(input, true);
+		for i in 0..3_u16 {
+			assert_eq!(u16::deserialize(&mut deserializer).unwrap(), i);
+			deserializer.read_end_marker().unwrap();
+		}
+	}
+
+	#[test]
+	fn test_truncated_number() {
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		Serializer::write_bytes(buf, &0x0102030405060708_u64, false).unwrap();
+		assert_eq!(
+			Deserializer::read_bytes::<u64>(&mut &buf[..5], false),
+			Err(BinaryError::InvalidLength {
+				actual: 5,
+				expected: 8
+			})
+		);
+	}
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct TestV2 {
+		pub byte: u8,
+		pub string: String,
+		pub extra: u8,
+	}
+
+	#[test]
+	fn test_extra_fields() {
+		let buf: &mut Vec<u8> = &mut Vec::new();
+		let value = TestV2 {
+			byte: 0x41,
+			string: String::from("test"),
+			extra: 0x42,
+		};
+		Serializer::write_bytes(buf, &value, false).unwrap();
+		assert_eq!(
+			Deserializer::read_bytes::<Test>(&mut buf.as_slice(), false),
+			Err(BinaryError::InvalidLength {
+				actual: 3,
+				expected: 2
+			})
+		);
+	}
 }
@@ -1,15 +1,19 @@
 use crate::serde_binary_adv::common::{
 	decompress_usize,
-	flags::{NONE, NONUNIT_VARIANT, SOME, STRUCT_VARIANT, UNIT_VARIANT},
+	flags::{END, NONE, NONUNIT_VARIANT, SOME, STRUCT_VARIANT, UNIT_VARIANT},
 };
 
 use super::super::BinaryError;
 use super::super::Result;
-use serde::de::{
-	self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, VariantAccess, Visitor,
+use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, VariantAccess, Visitor};
+use serde::{
+	Deserialize,
+	de::{DeserializeOwned, SeqAccess},
 };
-use serde::{Deserialize, de::SeqAccess};
-use std::io::Read;
+use std::io::{ErrorKind, Read};
+
+// The most bytes allocated before they are read, like the limit serde puts on size hints
+const MAX_PREALLOCATION: usize = 1024 * 1024;
 
 macro_rules! impl_deserialize_num {
 	($name:ident, $ty:ty, $visit:ident) => {
@@ -17,12 +21,7 @@ macro_rules! impl_deserialize_num {
 		where
 			V: Visitor<'de>,
 		{
-			let bytes: Vec<u8> = match self.take(size_of::<$ty>()) {
-				Ok(v) => v,
-				Err(e) => {
-					return Err(e);
-				}
-			};
+			let bytes: Vec<u8> = self.take_num(size_of::<$ty>())?;
 
 			let value: $ty = if self.big_endian {
 				<$ty>::from_be_bytes(match bytes.try_into() {
@@ -52,14 +51,7 @@ macro_rules! impl_deserialize_num {
 macro_rules! impl_next_uxx {
 	($name:ident, $ty:ty) => {
 		fn $name(&mut self) -> Result<$ty> {
-			let bytes = match self.take(size_of::<$ty>()) {
-				Ok(v) => v,
-				Err(e) => {
-					return Err(BinaryError::Message {
-						message: format!("{:?}", e),
-					});
-				}
-			};
+			let bytes = self.take_num(size_of::<$ty>())?;
 			Ok(if self.big_endian {
 				<$ty>::from_be_bytes(match bytes.try_into() {
 					Ok(v) => v,
@@ -83,15 +75,55 @@ macro_rules! impl_next_uxx {
 	};
 }
 
+/// Adapts an iterator of bytes to `Read`
+struct IterReader<I>(I);
+
+impl<I: Iterator<Item = u8>> Read for IterReader<I> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let mut count = 0;
+		for slot in buf.iter_mut() {
+			match self.0.next() {
+				Some(b) => *slot = b,
+				None => break,
+			}
+			count += 1;
+		}
+		Ok(count)
+	}
+}
+
 /// Deserializes binary data into Rust types
-pub struct Deserializer<'de> {
-	input: &'de mut dyn Read,
+///
+/// The Deserializer borrows a reader, which may be a trait object such as `dyn Read`. It is
+/// `Send` when the reader is, so use a `Send` reader, or `dyn Read + Send`, to move it to
+/// another thread.
+///
+/// A non-blocking reader with no data ready causes `BinaryError::WouldBlock`. The bytes of the
+/// value read before then have been consumed, so keep a copy of the input to start the value
+/// again from.
+pub struct Deserializer<'de, R: Read + ?Sized> {
+	input: &'de mut R,
 	big_endian: bool,
 }
 
-impl<'de> Deserializer<'de> {
+impl Deserializer<'_, dyn Read> {
+	/// Deserializes bytes pulled one at a time from an iterator into Rust structures.
+	///
+	/// Only the bytes needed for the value are taken from the iterator.
+	pub fn from_iter<I, T>(iter: I, big_endian: bool) -> Result<T>
+	where
+		I: IntoIterator<Item = u8>,
+		T: DeserializeOwned,
+	{
+		let mut reader = IterReader(iter.into_iter());
+		let mut deserializer = Deserializer::new(&mut reader, big_endian);
+		T::deserialize(&mut deserializer)
+	}
+}
+
+impl<'de, R: Read + ?Sized> Deserializer<'de, R> {
 	/// Deserializes a vector of bytes (`Vec<u8>`) into Rust structures.
-	pub fn read_bytes<T>(input: &'de mut dyn Read, big_endian: bool) -> Result<T>
+	pub fn read_bytes<T>(input: &'de mut R, big_endian: bool) -> Result<T>
 	where
 		T: Deserialize<'de>,
 	{
@@ -101,13 +133,36 @@ impl<'de> Deserializer<'de> {
 		Ok(t)
 	}
 
+	/// Deserializes a value written with `Serializer::write_bytes_with_end_marker`, returning
+	/// `BinaryError::UnexpectedEndOfInput` if the stream ends before the end marker
+	pub fn read_bytes_with_end_marker<T>(input: &'de mut R, big_endian: bool) -> Result<T>
+	where
+		T: Deserialize<'de>,
+	{
+		let mut deserializer = Deserializer::new(input, big_endian);
+		let t = T::deserialize(&mut deserializer)?;
+		deserializer.read_end_marker()?;
+		Ok(t)
+	}
+
 	/// Creates a binary deserializer
-	pub fn new(input: &'de mut dyn Read, big_endian: bool) -> Deserializer<'de> {
+	pub fn new(input: &'de mut R, big_endian: bool) -> Self {
 		Deserializer { input, big_endian }
 	}
 
+	/// Reads the end marker written by `Serializer::write_end_marker`
+	pub fn read_end_marker(&mut self) -> Result<()> {
+		match self.next()? {
+			END => Ok(()),
+			v => Err(BinaryError::MissingOrInvalidFlag {
+				actual: v,
+				expected: END,
+			}),
+		}
+	}
+
 	fn next(&mut self) -> Result<u8> {
-		let buf: &mut [u8] = &mut [0x00 as u8];
+		let buf: &mut [u8] = &mut [0x00_u8];
 		match self.input.read(buf) {
 			Ok(v) => {
 				if v < 1 {
@@ -116,26 +171,39 @@ impl<'de> Deserializer<'de> {
 					Ok(buf[0])
 				}
 			}
-			Err(e) => Err(BinaryError::Message {
-				message: e.to_string(),
-			}),
+			Err(e) => Err(e.into()),
 		}
 	}
 
 	fn take(&mut self, len: usize) -> Result<Vec<u8>> {
-		let buf: &mut Vec<u8> = &mut vec![0x00 as u8; len];
-		match self.input.read(buf) {
-			Ok(v) => {
-				if v < len {
-					Err(BinaryError::UnexpectedEndOfInput)
-				} else {
-					Ok(buf.to_vec())
+		// The length may come from the data, so the buffer only grows as the bytes arrive
+		let mut buf: Vec<u8> = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+		while buf.len() < len {
+			let start = buf.len();
+			buf.resize(start + (len - start).min(MAX_PREALLOCATION), 0x00);
+			self.input.read_exact(&mut buf[start..])?;
+		}
+		Ok(buf)
+	}
+
+	// Takes the bytes of a number, reporting its width if the input ends first
+	fn take_num(&mut self, len: usize) -> Result<Vec<u8>> {
+		let mut buf: Vec<u8> = vec![0x00_u8; len];
+		let mut read = 0;
+		while read < len {
+			match self.input.read(&mut buf[read..]) {
+				Ok(0) => {
+					return Err(BinaryError::InvalidLength {
+						actual: read,
+						expected: len,
+					});
 				}
+				Ok(v) => read += v,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e.into()),
 			}
-			Err(e) => Err(BinaryError::Message {
-				message: e.to_string(),
-			}),
 		}
+		Ok(buf)
 	}
 
 	impl_next_uxx!(next_u32, u32);
@@ -151,7 +219,7 @@ impl<'de> Deserializer<'de> {
 				}
 			}
 		}
-		Ok(decompress_usize(&bytes)?)
+		decompress_usize(&bytes)
 	}
 
 	fn take_string(&mut self) -> Result<String> {
@@ -165,7 +233,7 @@ impl<'de> Deserializer<'de> {
 	}
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de, R: Read + ?Sized> de::Deserializer<'de> for &mut Deserializer<'de, R> {
 	type Error = BinaryError;
 
 	impl_deserialize_num!(deserialize_u16, u16, visit_u16);
@@ -246,7 +314,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		V: Visitor<'de>,
 	{
 		let s = self.take_string()?;
-		visitor.visit_str(&s.as_str())
+		visitor.visit_str(s.as_str())
 	}
 
 	fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -256,18 +324,19 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		self.deserialize_str(visitor)
 	}
 
-	fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+	fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		unimplemented!()
+		self.deserialize_byte_buf(visitor)
 	}
 
-	fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+	fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		unimplemented!()
+		let len = self.next_usize()?;
+		visitor.visit_byte_buf(self.take(len)?)
 	}
 
 	fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
@@ -280,10 +349,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		} else if flag == SOME {
 			visitor.visit_some(self)
 		} else {
-			Err(BinaryError::MissingOrInvalidFlag {
-				actual: flag,
-				expected: SOME,
-			})
+			Err(BinaryError::InvalidOptionFlag { actual: flag })
 		}
 	}
 
@@ -317,23 +383,30 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		visitor.visit_seq(BinarySeries::new(&mut *self, len))
 	}
 
-	fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+	fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		self.deserialize_seq(visitor)
+		let actual: usize = self.next_usize()?;
+		if actual != len {
+			return Err(BinaryError::InvalidLength {
+				actual,
+				expected: len,
+			});
+		}
+		visitor.visit_seq(BinarySeries::new(&mut *self, len))
 	}
 
 	fn deserialize_tuple_struct<V>(
 		self,
 		_name: &'static str,
-		_len: usize,
+		len: usize,
 		visitor: V,
 	) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		self.deserialize_seq(visitor)
+		self.deserialize_tuple(len, visitor)
 	}
 
 	fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
@@ -347,35 +420,45 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 	fn deserialize_struct<V>(
 		self,
 		_name: &'static str,
-		_fields: &'static [&'static str],
+		fields: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
 		let len = self.next_usize()?;
+		// Extra fields cannot be skipped without knowing their types
+		if len > fields.len() {
+			return Err(BinaryError::InvalidLength {
+				actual: len,
+				expected: fields.len(),
+			});
+		}
 		visitor.visit_seq(BinarySeries::new(&mut *self, len))
 	}
 
 	fn deserialize_enum<V>(
 		self,
-		_name: &'static str,
+		name: &'static str,
 		variants: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
+		// An enum without variants has no values, so there is nothing that could be read
+		if variants.is_empty() {
+			return Err(BinaryError::Message {
+				message: format!("enum {} has no variants", name),
+			});
+		}
 		let variant_type = self.next()?;
 
 		match variant_type {
-			NONUNIT_VARIANT => visitor.visit_enum(Enum::new(self)),
-			STRUCT_VARIANT => visitor.visit_enum(Enum::new(self)),
-			UNIT_VARIANT => {
-				let variant_index: u32 = self.next_u32()?;
-				let variant: &'de str = variants[variant_index as usize];
-
-				visitor.visit_enum(variant.into_deserializer())
+			// The variant index is passed to the visitor as is, rather than looked up in
+			// `variants`, so that `#[serde(other)]` can handle indexes beyond the known set
+			UNIT_VARIANT | NONUNIT_VARIANT | STRUCT_VARIANT => {
+				visitor.visit_enum(Enum::new(self, variant_type))
 			}
 			_ => Err(BinaryError::MissingOrInvalidFlag {
 				actual: variant_type,
@@ -391,29 +474,38 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 		visitor.visit_u32(self.next_u32()?)
 	}
 
-	fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+	fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
 	where
 		V: Visitor<'de>,
 	{
-		unimplemented!()
+		self.deserialize_any(visitor)
 	}
 
 	fn deserialize_any<V>(self, _visitor: V) -> std::result::Result<V::Value, Self::Error>
 	where
 		V: Visitor<'de>,
 	{
-		unimplemented!()
+		Err(BinaryError::Message {
+			message: String::from(
+				"deserialize_any is not supported by the streaming Deserializer; types such as \
+				 untagged enums require the self-describing mode of the in-memory Deserializer",
+			),
+		})
+	}
+
+	fn is_human_readable(&self) -> bool {
+		false
 	}
 }
 
-struct BinarySeries<'a, 'de: 'a> {
-	de: &'a mut Deserializer<'de>,
+struct BinarySeries<'a, 'de: 'a, R: Read + ?Sized> {
+	de: &'a mut Deserializer<'de, R>,
 	len: usize,
 	position: usize,
 }
 
-impl<'a, 'de> BinarySeries<'a, 'de> {
-	pub fn new(de: &'a mut Deserializer<'de>, len: usize) -> Self {
+impl<'a, 'de, R: Read + ?Sized> BinarySeries<'a, 'de, R> {
+	pub fn new(de: &'a mut Deserializer<'de, R>, len: usize) -> Self {
 		Self {
 			de,
 			len,
@@ -422,7 +514,7 @@ impl<'a, 'de> BinarySeries<'a, 'de> {
 	}
 }
 
-impl<'de, 'a> SeqAccess<'de> for BinarySeries<'a, 'de> {
+impl<'de, 'a, R: Read + ?Sized> SeqAccess<'de> for BinarySeries<'a, 'de, R> {
 	type Error = BinaryError;
 
 	fn next_element_seed<T>(
@@ -443,9 +535,15 @@ impl<'de, 'a> SeqAccess<'de> for BinarySeries<'a, 'de> {
 		self.position += 1;
 		seed.deserialize(&mut *self.de).map(Some)
 	}
+
+	// Serde caps the capacity it reserves from a hint, so a huge length cannot force a huge
+	// allocation before the entries are read
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.len.saturating_sub(self.position))
+	}
 }
 
-impl<'de, 'a> MapAccess<'de> for BinarySeries<'a, 'de> {
+impl<'de, 'a, R: Read + ?Sized> MapAccess<'de> for BinarySeries<'a, 'de, R> {
 	type Error = BinaryError;
 
 	fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
@@ -470,19 +568,35 @@ impl<'de, 'a> MapAccess<'de> for BinarySeries<'a, 'de> {
 	{
 		seed.deserialize(&mut *self.de)
 	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.len.saturating_sub(self.position))
+	}
 }
 
-struct Enum<'a, 'de: 'a> {
-	de: &'a mut Deserializer<'de>,
+struct Enum<'a, 'de: 'a, R: Read + ?Sized> {
+	de: &'a mut Deserializer<'de, R>,
+	variant_type: u8,
 }
 
-impl<'a, 'de> Enum<'a, 'de> {
-	fn new(de: &'a mut Deserializer<'de>) -> Self {
-		Enum { de }
+impl<'a, 'de, R: Read + ?Sized> Enum<'a, 'de, R> {
+	fn new(de: &'a mut Deserializer<'de, R>, variant_type: u8) -> Self {
+		Enum { de, variant_type }
+	}
+
+	fn expect(&self, expected: u8) -> Result<()> {
+		if self.variant_type == expected {
+			Ok(())
+		} else {
+			Err(BinaryError::MissingOrInvalidFlag {
+				actual: self.variant_type,
+				expected,
+			})
+		}
 	}
 }
 
-impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
+impl<'de, 'a, R: Read + ?Sized> EnumAccess<'de> for Enum<'a, 'de, R> {
 	type Error = BinaryError;
 	type Variant = Self;
 
@@ -494,17 +608,22 @@ impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
 	}
 }
 
-impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
+impl<'de, 'a, R: Read + ?Sized> VariantAccess<'de> for Enum<'a, 'de, R> {
 	type Error = BinaryError;
 
 	fn unit_variant(self) -> Result<()> {
-		Err(BinaryError::UnexpectedType)
+		if self.variant_type == UNIT_VARIANT {
+			Ok(())
+		} else {
+			Err(BinaryError::UnexpectedType)
+		}
 	}
 
 	fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
 	where
 		T: DeserializeSeed<'de>,
 	{
+		self.expect(NONUNIT_VARIANT)?;
 		seed.deserialize(self.de)
 	}
 
@@ -512,6 +631,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
 	where
 		V: Visitor<'de>,
 	{
+		self.expect(NONUNIT_VARIANT)?;
 		de::Deserializer::deserialize_seq(self.de, visitor)
 	}
 
@@ -519,6 +639,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
 	where
 		V: Visitor<'de>,
 	{
+		self.expect(STRUCT_VARIANT)?;
 		de::Deserializer::deserialize_seq(self.de, visitor)
 	}
 }
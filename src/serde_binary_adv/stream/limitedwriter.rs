@@ -0,0 +1,90 @@
+//! A writer that bounds the number of bytes written to another writer.
+
+use std::io::{self, Write};
+
+use crate::BinaryError;
+
+/// Wraps a writer and fails once more than a set number of bytes would be written to it.
+///
+/// A write that would exceed the limit is rejected as a whole, so the inner writer only ever
+/// receives complete writes. The Serializer reports the failure as `BinaryError::BufferTooSmall`.
+pub struct LimitedWriter<W: Write> {
+	inner: W,
+	limit: usize,
+	written: usize,
+}
+
+impl<W: Write> LimitedWriter<W> {
+	/// Creates a new LimitedWriter that accepts at most `limit` bytes
+	pub fn new(inner: W, limit: usize) -> Self {
+		Self {
+			inner,
+			limit,
+			written: 0,
+		}
+	}
+
+	/// The number of bytes written so far
+	pub fn written(&self) -> usize {
+		self.written
+	}
+
+	/// The maximum number of bytes that can be written
+	pub fn limit(&self) -> usize {
+		self.limit
+	}
+
+	/// Returns the inner writer
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if buf.len() > self.limit - self.written {
+			return Err(io::Error::other(BinaryError::BufferTooSmall));
+		}
+		let len = self.inner.write(buf)?;
+		self.written += len;
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use super::LimitedWriter;
+	use crate::BinaryError;
+	use crate::stream::Serializer;
+
+	#[test]
+	fn test_within_limit() {
+		let mut writer = LimitedWriter::new(Vec::new(), 5);
+		Serializer::write_bytes(&mut writer, &String::from("test"), false).unwrap();
+		assert_eq!(writer.written(), 5);
+		assert_eq!(writer.into_inner(), vec![0x04, b't', b'e', b's', b't']);
+	}
+
+	#[test]
+	fn test_over_limit() {
+		let mut writer = LimitedWriter::new(Vec::new(), 8);
+		let result = Serializer::write_bytes(&mut writer, &vec![0x41_u32, 0x42, 0x43], false);
+		assert_eq!(result, Err(BinaryError::BufferTooSmall));
+		// The length and the first element fit, the second element does not
+		assert_eq!(writer.written(), 5);
+	}
+
+	#[test]
+	fn test_write() {
+		let mut writer = LimitedWriter::new(Vec::new(), 2);
+		assert!(writer.write_all(&[0x41, 0x42]).is_ok());
+		assert!(writer.write_all(&[0x43]).is_err());
+		assert_eq!(writer.limit(), 2);
+	}
+}
@@ -0,0 +1,259 @@
+//! Write a sequence to a stream one element at a time, such as an append-only log, and read it
+//! back the same way.
+
+use std::{
+	io::{self, Read, Seek, SeekFrom, Write},
+	marker::PhantomData,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::{Deserializer, Serializer};
+use crate::{
+	BinaryError, Result,
+	serde_binary_adv::common::flags::{END, SOME},
+};
+
+// The count written in place of the number of elements when it follows them instead
+const TRAILER: u64 = u64::MAX;
+
+type PatchFn<W> = fn(&mut W, u64, &[u8]) -> io::Result<()>;
+
+/// Writes the elements of a sequence one at a time, preceded by their number.
+///
+/// The number of elements is only known once they have all been written, so it is filled in by
+/// `finish`. A writer that can seek, created with `new`, has the number written over a
+/// placeholder before the elements. Any other writer, created with `with_trailer`, has each
+/// element preceded by a `0xFF` flag and the number written after them, following an end marker.
+/// The number is a `u64` in the byte order of the writer.
+///
+/// A sequence that is never finished reads as empty if written with `new`, and as its elements
+/// followed by an error if written with `with_trailer`.
+pub struct SeqWriter<W: Write> {
+	output: W,
+	big_endian: bool,
+	count: u64,
+	// The position of the placeholder, and how to write over it, when the writer can seek
+	patch: Option<(u64, PatchFn<W>)>,
+}
+
+impl<W: Write + Seek> SeqWriter<W> {
+	/// Creates a SeqWriter that writes the number of elements before them, starting at the
+	/// current position of the writer
+	pub fn new(mut output: W, big_endian: bool) -> Result<Self> {
+		let position = output.stream_position()?;
+		Serializer::write_bytes(&mut output, &0_u64, big_endian)?;
+		let patch: PatchFn<W> = |output, position, bytes| {
+			let end = output.stream_position()?;
+			output.seek(SeekFrom::Start(position))?;
+			output.write_all(bytes)?;
+			output.seek(SeekFrom::Start(end))?;
+			Ok(())
+		};
+		Ok(Self {
+			output,
+			big_endian,
+			count: 0,
+			patch: Some((position, patch)),
+		})
+	}
+}
+
+impl<W: Write> SeqWriter<W> {
+	/// Creates a SeqWriter that writes the number of elements after them, for writers that
+	/// cannot seek
+	pub fn with_trailer(mut output: W, big_endian: bool) -> Result<Self> {
+		Serializer::write_bytes(&mut output, &TRAILER, big_endian)?;
+		Ok(Self {
+			output,
+			big_endian,
+			count: 0,
+			patch: None,
+		})
+	}
+
+	/// Writes an element to the end of the sequence
+	pub fn write_element<T>(&mut self, value: &T) -> Result<()>
+	where
+		T: ?Sized + Serialize,
+	{
+		if self.patch.is_none() {
+			Serializer::write_bytes(&mut self.output, &SOME, self.big_endian)?;
+		}
+		Serializer::write_bytes(&mut self.output, value, self.big_endian)?;
+		self.count += 1;
+		Ok(())
+	}
+
+	/// The number of elements written so far
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+
+	/// Writes the number of elements and returns the writer
+	pub fn finish(mut self) -> Result<W> {
+		match self.patch {
+			Some((position, patch)) => {
+				let mut count: Vec<u8> = Vec::new();
+				Serializer::write_bytes(&mut count, &self.count, self.big_endian)?;
+				patch(&mut self.output, position, &count)?;
+			}
+			None => {
+				Serializer::write_bytes(&mut self.output, &END, self.big_endian)?;
+				Serializer::write_bytes(&mut self.output, &self.count, self.big_endian)?;
+			}
+		}
+		Ok(self.output)
+	}
+}
+
+/// Reads the elements of a sequence written by a `SeqWriter`, one each time it is advanced.
+///
+/// The iterator ends after the first error.
+pub struct SeqReader<R: Read, T> {
+	input: R,
+	big_endian: bool,
+	// The number of elements left, or `None` when the number follows them
+	remaining: Option<u64>,
+	read: u64,
+	done: bool,
+	element: PhantomData<T>,
+}
+
+impl<R: Read, T: DeserializeOwned> SeqReader<R, T> {
+	/// Creates a SeqReader, reading the number of elements if it comes before them
+	pub fn new(mut input: R, big_endian: bool) -> Result<Self> {
+		let count: u64 = Deserializer::read_bytes(&mut input, big_endian)?;
+		Ok(Self {
+			input,
+			big_endian,
+			remaining: (count != TRAILER).then_some(count),
+			read: 0,
+			done: false,
+			element: PhantomData,
+		})
+	}
+
+	/// Returns the reader
+	pub fn into_inner(self) -> R {
+		self.input
+	}
+
+	fn read_element(&mut self) -> Result<Option<T>> {
+		match self.remaining {
+			Some(0) => return Ok(None),
+			Some(ref mut remaining) => *remaining -= 1,
+			None => match Deserializer::read_bytes(&mut self.input, self.big_endian)? {
+				SOME => {}
+				END => {
+					let count: u64 = Deserializer::read_bytes(&mut self.input, self.big_endian)?;
+					if count != self.read {
+						return Err(BinaryError::Message {
+							message: format!(
+								"read {} elements, but {} were written",
+								self.read, count
+							),
+						});
+					}
+					return Ok(None);
+				}
+				actual => {
+					return Err(BinaryError::MissingOrInvalidFlag {
+						actual,
+						expected: SOME,
+					});
+				}
+			},
+		}
+		self.read += 1;
+		Deserializer::read_bytes(&mut self.input, self.big_endian).map(Some)
+	}
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for SeqReader<R, T> {
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+		let element = self.read_element().transpose();
+		self.done = !matches!(element, Some(Ok(_)));
+		element
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use serde::{Deserialize, Serialize};
+
+	use super::{SeqReader, SeqWriter};
+	use crate::BinaryError;
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct Entry {
+		pub id: u32,
+		pub message: String,
+	}
+
+	fn entry(id: u32) -> Entry {
+		Entry {
+			id,
+			message: format!("entry {}", id),
+		}
+	}
+
+	#[test]
+	fn test_seekable() {
+		for big_endian in [false, true] {
+			let mut writer = SeqWriter::new(Cursor::new(Vec::new()), big_endian).unwrap();
+			for id in 0..100 {
+				writer.write_element(&entry(id)).unwrap();
+			}
+			assert_eq!(writer.count(), 100);
+			let buf = writer.finish().unwrap().into_inner();
+			let count = if big_endian { buf[7] } else { buf[0] };
+			assert_eq!(count, 100);
+
+			let reader = SeqReader::new(buf.as_slice(), big_endian).unwrap();
+			let entries: Vec<Entry> = reader.map(|e| e.unwrap()).collect();
+			assert_eq!(entries, (0..100).map(entry).collect::<Vec<Entry>>());
+		}
+	}
+
+	#[test]
+	fn test_trailer() {
+		let mut writer = SeqWriter::with_trailer(Vec::new(), false).unwrap();
+		for id in 0..100 {
+			writer.write_element(&entry(id)).unwrap();
+		}
+		let buf = writer.finish().unwrap();
+		let reader = SeqReader::new(buf.as_slice(), false).unwrap();
+		let entries: Vec<Entry> = reader.map(|e| e.unwrap()).collect();
+		assert_eq!(entries, (0..100).map(entry).collect::<Vec<Entry>>());
+
+		// A sequence that was not finished
+		let mut writer = SeqWriter::with_trailer(Vec::new(), false).unwrap();
+		writer.write_element(&entry(0)).unwrap();
+		let mut reader: SeqReader<_, Entry> =
+			SeqReader::new(writer.output.as_slice(), false).unwrap();
+		assert_eq!(reader.next(), Some(Ok(entry(0))));
+		assert_eq!(reader.next(), Some(Err(BinaryError::UnexpectedEndOfInput)));
+		assert_eq!(reader.next(), None);
+	}
+
+	#[test]
+	fn test_appended() {
+		// The sequence starts after whatever the writer already holds
+		let mut cursor = Cursor::new(vec![0x41, 0x42]);
+		cursor.set_position(2);
+		let mut writer = SeqWriter::new(cursor, false).unwrap();
+		writer.write_element(&0x43_u8).unwrap();
+		let buf = writer.finish().unwrap().into_inner();
+		assert_eq!(buf, vec![0x41, 0x42, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x43]);
+		let reader: SeqReader<_, u8> = SeqReader::new(&buf[2..], false).unwrap();
+		assert_eq!(reader.collect::<Vec<_>>(), vec![Ok(0x43)]);
+	}
+}
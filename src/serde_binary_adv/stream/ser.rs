@@ -1,6 +1,6 @@
 //! Serialize a Rust structure into a binary data stream.
 
-use std::io::Write;
+use std::io::{Cursor, Write};
 
 use super::super::Result;
 use crate::{
@@ -14,59 +14,133 @@ use num::traits::ToBytes;
 use serde::{Serialize, ser};
 
 /// A structure for serializing Rust values into binary.
-pub struct Serializer<'se> {
-	output: &'se mut dyn Write,
+///
+/// The Serializer either borrows the writer, as in `Serializer::new(&mut writer, false)`, or owns
+/// it, in which case `into_inner` returns it once serialization is done. The writer may be a trait
+/// object such as `dyn Write`. The Serializer is `Send` when the writer is, so use a `Send` writer,
+/// or `dyn Write + Send`, to move it to another thread.
+pub struct Serializer<W: Write> {
+	output: W,
 	big_endian: bool,
-	unsized_seq: bool,
 }
 
-impl<'se> Serializer<'se> {
-	/// Converts a Rust value into a binary representation and returns a `Vec<u8>` of the bytes
-	pub fn write_bytes<T>(output: &'se mut impl Write, value: &T, big_endian: bool) -> Result<()>
+impl<'se, W: Write + ?Sized> Serializer<&'se mut W> {
+	/// Converts a Rust value into a binary representation and writes it to `output`
+	pub fn write_bytes<T>(output: &'se mut W, value: &T, big_endian: bool) -> Result<()>
 	where
-		T: Serialize,
+		T: ?Sized + Serialize,
 	{
 		let mut serializer = Self::new(output, big_endian);
 		value.serialize(&mut serializer)?;
 		Ok(())
 	}
 
-	/// Creates a new binary Serializer
-	pub fn new(output: &'se mut impl Write, big_endian: bool) -> Self {
-		Self {
-			output,
-			big_endian,
-			unsized_seq: false,
-		}
+	/// Converts a Rust value into a binary representation and writes it to `output`, followed by
+	/// the end marker, so that a reader can tell the value was written in full
+	pub fn write_bytes_with_end_marker<T>(
+		output: &'se mut W,
+		value: &T,
+		big_endian: bool,
+	) -> Result<()>
+	where
+		T: ?Sized + Serialize,
+	{
+		let mut serializer = Self::new(output, big_endian);
+		value.serialize(&mut serializer)?;
+		serializer.write_end_marker()
+	}
+
+	/// Creates a new binary Serializer that borrows the writer
+	pub fn new(output: &'se mut W, big_endian: bool) -> Self {
+		Self::from_writer(output, big_endian)
+	}
+}
+
+impl Serializer<Cursor<&mut [u8]>> {
+	/// Converts a Rust value into a binary representation and writes it into the cursor's buffer
+	/// at the cursor position, returning the position afterwards.
+	///
+	/// This suits writing into a fixed buffer such as `Cursor<&mut [u8; 64]>`, where the position
+	/// is the number of bytes used. The buffer is never grown: running out of room is an error,
+	/// and leaves the cursor after the bytes that fit.
+	pub fn write_to_cursor<C, T>(cursor: &mut Cursor<C>, value: &T, big_endian: bool) -> Result<u64>
+	where
+		C: AsMut<[u8]>,
+		T: ?Sized + Serialize,
+	{
+		let start = cursor.position();
+		let buf = cursor.get_mut().as_mut();
+		let offset = usize::try_from(start).map_or(buf.len(), |v| v.min(buf.len()));
+		let mut serializer = Serializer::from_writer(Cursor::new(&mut buf[offset..]), big_endian);
+		let res = serializer.serialize(value);
+		let written = serializer.into_inner().position();
+		cursor.set_position(start + written);
+		res?;
+		Ok(cursor.position())
+	}
+}
+
+impl<W: Write> Serializer<W> {
+	/// Creates a new binary Serializer that owns the writer
+	pub fn from_writer(output: W, big_endian: bool) -> Self {
+		Self { output, big_endian }
+	}
+
+	/// Serializes a value to the writer, so several values can be written one after another
+	pub fn serialize<T>(&mut self, value: &T) -> Result<()>
+	where
+		T: ?Sized + Serialize,
+	{
+		value.serialize(self)
 	}
 
-	fn write(&mut self, data: &Vec<u8>) {
-		self.output.write(data.as_slice()).unwrap();
+	/// Writes the end marker, which `stream::Deserializer::read_end_marker` checks for
+	pub fn write_end_marker(&mut self) -> Result<()> {
+		self.write(&[flags::END])
 	}
 
-	fn serialize_num<T: ToBytes>(self: &mut Self, v: T) -> Result<()> {
+	/// Returns the writer
+	pub fn into_inner(self) -> W {
+		self.output
+	}
+
+	fn write(&mut self, data: &[u8]) -> Result<()> {
+		Ok(self.output.write_all(data)?)
+	}
+
+	fn serialize_num<T: ToBytes>(&mut self, v: T) -> Result<()> {
 		if self.big_endian {
-			self.write(&v.to_be_bytes().as_mut().to_vec());
+			self.write(v.to_be_bytes().as_ref())
 		} else {
-			self.write(&v.to_le_bytes().as_mut().to_vec());
+			self.write(v.to_le_bytes().as_ref())
 		}
-		Ok(())
 	}
 
-	fn serialize_vec<T: ToBytes>(self: &mut Self, v: Vec<T>) -> Result<()> {
-		for item in v {
-			self.serialize_num(item)?;
-		}
-		Ok(())
+	fn serialize_slice(&mut self, v: &[u8]) -> Result<()> {
+		self.write(v)
 	}
 
 	fn serialize_usize(&mut self, v: usize) -> Result<()> {
-		self.serialize_vec(compress_usize(v))?;
+		// Most lengths fit in a single byte, which is written without allocating
+		if v <= 0x7F {
+			return self.write(&[v as u8]);
+		}
+		self.serialize_slice(&compress_usize(v))?;
 		Ok(())
 	}
 }
 
-impl<'se> ser::Serializer for &mut Serializer<'se> {
+// The length is written before the elements, and a stream cannot go back to write it once they
+// have been counted
+fn unknown_length() -> BinaryError {
+	BinaryError::Message {
+		message: String::from(
+			"the length of a sequence or map must be known to write it to a stream",
+		),
+	}
+}
+
+impl<W: Write> ser::Serializer for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -132,16 +206,17 @@ impl<'se> ser::Serializer for &mut Serializer<'se> {
 
 	fn serialize_char(self, v: char) -> Result<Self::Ok> {
 		let mut buf: [u8; 4] = [0, 0, 0, 0];
-		self.serialize_vec(v.encode_utf8(&mut buf).as_bytes().to_vec())
+		self.serialize_slice(v.encode_utf8(&mut buf).as_bytes())
 	}
 
 	fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-		self.serialize_usize(v.bytes().len()).unwrap();
-		self.serialize_vec(v.as_bytes().to_vec())
+		self.serialize_usize(v.len())?;
+		self.serialize_slice(v.as_bytes())
 	}
 
-	fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
-		unimplemented!()
+	fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+		self.serialize_usize(v.len())?;
+		self.serialize_slice(v)
 	}
 
 	fn serialize_none(self) -> Result<Self::Ok> {
@@ -152,7 +227,7 @@ impl<'se> ser::Serializer for &mut Serializer<'se> {
 	where
 		T: ?Sized + ser::Serialize,
 	{
-		self.serialize_u8(flags::SOME).unwrap();
+		self.serialize_u8(flags::SOME)?;
 		value.serialize(self)
 	}
 
@@ -170,7 +245,7 @@ impl<'se> ser::Serializer for &mut Serializer<'se> {
 		variant_index: u32,
 		_variant: &'static str,
 	) -> Result<Self::Ok> {
-		UNIT_VARIANT.serialize(&mut *self).unwrap();
+		UNIT_VARIANT.serialize(&mut *self)?;
 		variant_index.serialize(&mut *self)
 	}
 
@@ -191,20 +266,19 @@ impl<'se> ser::Serializer for &mut Serializer<'se> {
 	where
 		T: ?Sized + ser::Serialize,
 	{
-		NONUNIT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self).unwrap();
+		NONUNIT_VARIANT.serialize(&mut *self)?;
+		variant_index.serialize(&mut *self)?;
 		value.serialize(self)
 	}
 
 	fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
 		match len {
 			Some(n) => {
-				self.serialize_usize(n).unwrap();
-				self.unsized_seq = false;
+				self.serialize_usize(n)?;
+				Ok(self)
 			}
-			None => unimplemented!(),
+			None => Err(unknown_length()),
 		}
-		Ok(self)
 	}
 
 	fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
@@ -226,26 +300,24 @@ impl<'se> ser::Serializer for &mut Serializer<'se> {
 		_variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeTupleVariant> {
-		NONUNIT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self).unwrap();
-		self.serialize_usize(len).unwrap();
+		NONUNIT_VARIANT.serialize(&mut *self)?;
+		variant_index.serialize(&mut *self)?;
+		self.serialize_usize(len)?;
 		Ok(self)
 	}
 
 	fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
 		match len {
 			Some(n) => {
-				self.serialize_vec(compress_usize(n)).unwrap();
+				self.serialize_usize(n)?;
 				Ok(self)
 			}
-			// Serializing maps of unknown length to binary is difficult, since any value that
-			// can be used to mark the end of the sequence can also be a member
-			None => unimplemented!(),
+			None => Err(unknown_length()),
 		}
 	}
 
 	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-		self.serialize_usize(len).unwrap();
+		self.serialize_usize(len)?;
 		Ok(self)
 	}
 
@@ -256,14 +328,18 @@ impl<'se> ser::Serializer for &mut Serializer<'se> {
 		_variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeStructVariant> {
-		STRUCT_VARIANT.serialize(&mut *self).unwrap();
-		variant_index.serialize(&mut *self).unwrap();
-		self.serialize_usize(len).unwrap();
+		STRUCT_VARIANT.serialize(&mut *self)?;
+		variant_index.serialize(&mut *self)?;
+		self.serialize_usize(len)?;
 		Ok(self)
 	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
 }
 
-impl<'se> ser::SerializeSeq for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -280,7 +356,7 @@ impl<'se> ser::SerializeSeq for &mut Serializer<'se> {
 	}
 }
 
-impl<'se> ser::SerializeTuple for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -296,7 +372,7 @@ impl<'se> ser::SerializeTuple for &mut Serializer<'se> {
 	}
 }
 
-impl<'se> ser::SerializeTupleStruct for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -312,7 +388,7 @@ impl<'se> ser::SerializeTupleStruct for &mut Serializer<'se> {
 	}
 }
 
-impl<'se> ser::SerializeTupleVariant for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -328,7 +404,7 @@ impl<'se> ser::SerializeTupleVariant for &mut Serializer<'se> {
 	}
 }
 
-impl<'se> ser::SerializeMap for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -351,7 +427,7 @@ impl<'se> ser::SerializeMap for &mut Serializer<'se> {
 	}
 }
 
-impl<'se> ser::SerializeStruct for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
@@ -367,7 +443,7 @@ impl<'se> ser::SerializeStruct for &mut Serializer<'se> {
 	}
 }
 
-impl<'se> ser::SerializeStructVariant for &mut Serializer<'se> {
+impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
 	type Ok = ();
 	type Error = BinaryError;
 
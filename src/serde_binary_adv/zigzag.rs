@@ -0,0 +1,197 @@
+//! Serialize signed integers of small magnitude in fewer bytes using zigzag encoding.
+//!
+//! Use with `#[serde(with = "serde_binary_adv::zigzag")]` on an `i16`, `i32`, `i64` or `i128`
+//! field. Signed integers are normally written in full width in two's complement, so `-1` takes as
+//! many bytes as the largest value. Zigzag encoding maps values of small magnitude to small
+//! unsigned numbers (`0`, `-1`, `1`, `-2`, ... become `0`, `1`, `2`, `3`, ...), which are then
+//! written compressed like lengths, inside a byte blob.
+//!
+//! Values between `-64` and `63` take two bytes, the blob length and the value. The low 64 bits
+//! of the encoded value are written first, and the high 64 bits follow only when they are not
+//! zero, which only happens for `i128` values.
+
+use std::fmt;
+
+use serde::{
+	Deserializer, Serializer,
+	de::{self, Visitor},
+	ser,
+};
+
+use crate::serde_binary_adv::common::{compress_usize, compressed_usize_len, decompress_usize};
+
+/// A signed integer type that can be zigzag encoded
+pub trait ZigZagInteger: Copy + Into<i128> + TryFrom<i128> {}
+
+impl ZigZagInteger for i16 {}
+impl ZigZagInteger for i32 {}
+impl ZigZagInteger for i64 {}
+impl ZigZagInteger for i128 {}
+
+/// Serializes a signed integer as its compressed zigzag encoding
+pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+	T: ZigZagInteger,
+{
+	let value: i128 = (*value).into();
+	let encoded = ((value << 1) ^ (value >> 127)) as u128;
+	let low = usize::try_from(encoded as u64).map_err(ser::Error::custom)?;
+	let mut bytes = compress_usize(low);
+	let high = usize::try_from((encoded >> 64) as u64).map_err(ser::Error::custom)?;
+	if high != 0 {
+		bytes.append(&mut compress_usize(high));
+	}
+	serializer.serialize_bytes(&bytes)
+}
+
+/// Deserializes a signed integer from its compressed zigzag encoding
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: ZigZagInteger,
+{
+	deserializer.deserialize_byte_buf(ZigZagVisitor::<T>(std::marker::PhantomData))
+}
+
+struct ZigZagVisitor<T>(std::marker::PhantomData<T>);
+
+impl<T> ZigZagVisitor<T> {
+	fn next_u64<E: de::Error>(bytes: &[u8], offset: &mut usize) -> Result<u64, E> {
+		let remaining = &bytes[*offset..];
+		let len = compressed_usize_len(remaining).map_err(de::Error::custom)?;
+		if remaining.len() < len {
+			return Err(de::Error::invalid_length(
+				bytes.len(),
+				&"a complete zigzag integer",
+			));
+		}
+		let value = decompress_usize(&remaining[..len]).map_err(de::Error::custom)?;
+		*offset += len;
+		u64::try_from(value).map_err(de::Error::custom)
+	}
+}
+
+impl<'de, T> Visitor<'de> for ZigZagVisitor<T>
+where
+	T: ZigZagInteger,
+{
+	type Value = T;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a zigzag encoded integer")
+	}
+
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		let mut offset: usize = 0;
+		let mut encoded = u128::from(Self::next_u64(v, &mut offset)?);
+		if offset < v.len() {
+			encoded |= u128::from(Self::next_u64(v, &mut offset)?) << 64;
+		}
+		if offset != v.len() {
+			return Err(de::Error::invalid_length(v.len(), &self));
+		}
+		let value = ((encoded >> 1) as i128) ^ -((encoded & 1) as i128);
+		T::try_from(value).map_err(|_| de::Error::custom("zigzag encoded value is out of range"))
+	}
+
+	fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.visit_bytes(&v)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::{Deserializer, Serializer};
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+	struct Deltas {
+		#[serde(with = "crate::zigzag")]
+		pub short: i16,
+		#[serde(with = "crate::zigzag")]
+		pub int: i32,
+		#[serde(with = "crate::zigzag")]
+		pub long: i64,
+		#[serde(with = "crate::zigzag")]
+		pub wide: i128,
+	}
+
+	impl Deltas {
+		fn new(value: i16) -> Self {
+			Self {
+				short: value,
+				int: value.into(),
+				long: value.into(),
+				wide: value.into(),
+			}
+		}
+	}
+
+	#[test]
+	fn test_small() {
+		for (value, encoded) in [(0, 0x00), (-1, 0x01), (1, 0x02), (-64, 0x7F), (63, 0x7E)] {
+			let value = Deltas::new(value);
+			let serialized = Serializer::to_bytes(&value, false).unwrap();
+			// Struct length, then the blob length and a single byte for each field
+			assert_eq!(
+				serialized,
+				vec![
+					0x04, 0x01, encoded, 0x01, encoded, 0x01, encoded, 0x01, encoded
+				]
+			);
+			let deserialized: Deltas = Deserializer::from_bytes(&serialized, false).unwrap();
+			assert_eq!(value, deserialized);
+		}
+	}
+
+	#[test]
+	fn test_limits() {
+		let values = [
+			Deltas::new(i16::MIN),
+			Deltas::new(i16::MAX),
+			Deltas {
+				int: i32::MIN,
+				long: i64::MIN,
+				wide: i128::MIN,
+				..Deltas::new(0)
+			},
+			Deltas {
+				int: i32::MAX,
+				long: i64::MAX,
+				wide: i128::MAX,
+				..Deltas::new(0)
+			},
+		];
+		for value in values {
+			for big_endian in [false, true] {
+				let serialized = Serializer::to_bytes(&value, big_endian).unwrap();
+				let deserialized: Deltas =
+					Deserializer::from_bytes(&serialized, big_endian).unwrap();
+				assert_eq!(value, deserialized);
+			}
+		}
+		let serialized = Serializer::to_bytes(&values[2], false).unwrap();
+		// `i32::MIN` is encoded as `0xFFFFFFFF`, which compresses to five bytes
+		assert_eq!(&serialized[3..9], [0x05, 0xFF, 0x7F, 0xFF, 0xFF, 0x0F]);
+	}
+
+	#[test]
+	fn test_out_of_range() {
+		let serialized = Serializer::to_bytes(&Deltas::new(0), false).unwrap();
+		let mut wide = serialized.clone();
+		// An `i32::MAX + 1` in place of the `i16`
+		wide.splice(1..3, [0x05, 0x80, 0x60, 0x00, 0x00, 0x10]);
+		assert!(Deserializer::from_bytes::<Deltas>(&wide, false).is_err());
+		let mut truncated = serialized;
+		truncated[1] = 0x02;
+		assert!(Deserializer::from_bytes::<Deltas>(&truncated, false).is_err());
+	}
+}